@@ -0,0 +1,256 @@
+//! Running several selectors and keeping the best selection.
+//!
+//! No selector wins everywhere: CoinGrinder finds the lightest selection, knapsack the one
+//! closest to the target and single random draw the hardest to fingerprint. Like Bitcoin
+//! Core, [`select_coins_best`] runs each of them and keeps the selection with the lowest
+//! cost, reporting how every one of them did.
+
+use bitcoin::{Amount, FeeRate, SignedAmount, Weight};
+use rand::Rng;
+
+use crate::capabilities::Algorithm;
+use crate::coin_grinder::{select_coins_coin_grinder, LimitPolicy};
+use crate::knapsack::select_coins_knapsack;
+use crate::objective::Objective;
+use crate::srd::{generate_change_target, select_coins_srd, CHANGE_LOWER};
+use crate::target::check_target;
+use crate::{SelectionError, SelectionReport, WeightedUtxo};
+
+/// How one selector did.
+#[derive(Debug)]
+pub struct Attempt<'a, U> {
+    /// The selector.
+    pub algorithm: Algorithm,
+    /// Its selection or why it found none.
+    pub result: Result<SelectionReport<'a, U>, SelectionError>,
+    /// The cost of the selection under the objective, `None` if there is no selection or
+    /// the cost overflows.
+    pub cost: Option<SignedAmount>,
+}
+
+/// The winning selection and how every selector did.
+#[derive(Debug)]
+pub struct BestSelection<'a, U> {
+    /// The selection with the lowest cost.
+    pub report: SelectionReport<'a, U>,
+    /// The selector that found it.
+    pub algorithm: Algorithm,
+    /// Every selector run, in the order they ran, the winner included.
+    pub attempts: Vec<Attempt<'a, U>>,
+}
+
+/// Runs CoinGrinder, knapsack and single random draw and keeps the selection with the
+/// lowest cost under `objective`, usually [`Objective::MinimizeWaste`].
+///
+/// As in Bitcoin Core, the minimum change of CoinGrinder and knapsack is drawn with
+/// [`generate_change_target`] and single random draw leaves [`CHANGE_LOWER`], each plus
+/// `change_fee`, the fee for the change output. The selections may weigh at most
+/// `max_weight`. Ties go to the selector that ran first.
+///
+/// If no selection has a cost, returns the error of the first selector that found none, or
+/// [`SelectionError::Overflow`] if all of them found one but their costs overflow.
+pub fn select_coins_best<'a, U, R>(
+    target: Amount,
+    change_fee: Amount,
+    fee_rate: FeeRate,
+    max_weight: Weight,
+    objective: Objective,
+    pool: &'a [U],
+    rng: &mut R,
+) -> Result<BestSelection<'a, U>, SelectionError>
+where
+    U: WeightedUtxo,
+    R: Rng + ?Sized,
+{
+    check_target(target)?;
+    let min_change =
+        generate_change_target(target, change_fee, rng).ok_or(SelectionError::Overflow)?;
+    let srd_change = CHANGE_LOWER
+        .checked_add(change_fee)
+        .ok_or(SelectionError::Overflow)?;
+
+    let results = vec![
+        (
+            Algorithm::CoinGrinder,
+            select_coins_coin_grinder(
+                target,
+                min_change,
+                max_weight,
+                usize::MAX,
+                fee_rate,
+                LimitPolicy::ReturnBest,
+                pool,
+            ),
+        ),
+        (
+            Algorithm::Knapsack,
+            select_coins_knapsack(target, min_change, None, fee_rate, pool, rng)
+                .and_then(|report| within_weight(report, max_weight)),
+        ),
+        (
+            Algorithm::SingleRandomDraw,
+            select_coins_srd(
+                target,
+                srd_change,
+                max_weight,
+                usize::MAX,
+                fee_rate,
+                pool,
+                rng,
+            ),
+        ),
+    ];
+    let attempts: Vec<Attempt<'a, U>> = results
+        .into_iter()
+        .map(|(algorithm, result)| Attempt {
+            algorithm,
+            cost: result
+                .as_ref()
+                .ok()
+                .and_then(|report| objective.cost(report)),
+            result,
+        })
+        .collect();
+
+    let mut winner: Option<(&Attempt<'a, U>, SignedAmount)> = None;
+    for attempt in &attempts {
+        if let Some(cost) = attempt.cost {
+            if winner.map_or(true, |(_, best)| cost < best) {
+                winner = Some((attempt, cost));
+            }
+        }
+    }
+    let winner = winner.and_then(|(attempt, _)| match &attempt.result {
+        Ok(report) => Some((report.clone(), attempt.algorithm)),
+        Err(_) => None,
+    });
+    match winner {
+        Some((report, algorithm)) => Ok(BestSelection {
+            report,
+            algorithm,
+            attempts,
+        }),
+        None => Err(attempts
+            .into_iter()
+            .find_map(|attempt| attempt.result.err())
+            .unwrap_or(SelectionError::Overflow)),
+    }
+}
+
+/// Rejects knapsack selections heavier than `max_weight`, which knapsack doesn't limit.
+fn within_weight<U>(
+    report: SelectionReport<'_, U>,
+    max_weight: Weight,
+) -> Result<SelectionReport<'_, U>, SelectionError> {
+    if report.weight > max_weight {
+        Err(SelectionError::MaxWeightExceeded { max_weight })
+    } else {
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::defaults::MAX_STANDARD_TX_WEIGHT;
+    use crate::tests::build_pool;
+
+    const WASTE: Objective = Objective::MinimizeWaste {
+        long_term_fee_rate: FeeRate::from_sat_per_vb_u32(5),
+        change_cost: Amount::from_sat(2_000),
+        min_change: Amount::from_sat(1_000),
+    };
+
+    fn indices<U>(report: &SelectionReport<'_, U>) -> Vec<usize> {
+        report.selection.iter().map(|c| c.index).collect()
+    }
+
+    #[test]
+    fn lowest_cost_wins() {
+        let pool = build_pool(&[
+            (1_500_000, 272),
+            (700_000, 272),
+            (400_000, 272),
+            (250_000, 272),
+            (90_000, 272),
+        ]);
+        let mut rng = StdRng::seed_from_u64(42);
+        let best = select_coins_best(
+            Amount::from_sat(600_000),
+            Amount::from_sat(500),
+            FeeRate::from_sat_per_vb_u32(10),
+            MAX_STANDARD_TX_WEIGHT,
+            WASTE,
+            &pool,
+            &mut rng,
+        )
+        .unwrap();
+
+        let algorithms: Vec<Algorithm> = best.attempts.iter().map(|a| a.algorithm).collect();
+        assert_eq!(
+            algorithms,
+            vec![
+                Algorithm::CoinGrinder,
+                Algorithm::Knapsack,
+                Algorithm::SingleRandomDraw
+            ]
+        );
+        let lowest = best.attempts.iter().filter_map(|a| a.cost).min();
+        assert_eq!(WASTE.cost(&best.report), lowest);
+
+        let winner = best
+            .attempts
+            .iter()
+            .find(|a| a.algorithm == best.algorithm)
+            .unwrap();
+        assert_eq!(winner.cost, lowest);
+        assert_eq!(
+            winner.result.as_ref().map(indices).unwrap(),
+            indices(&best.report)
+        );
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        let pool = build_pool(&[(10_000, 0), (20_000, 0)]);
+        let mut rng = StdRng::seed_from_u64(42);
+        let best = select_coins_best(
+            Amount::from_sat(50_000),
+            Amount::ZERO,
+            FeeRate::ZERO,
+            MAX_STANDARD_TX_WEIGHT,
+            WASTE,
+            &pool,
+            &mut rng,
+        );
+        assert!(matches!(
+            best.map(|best| best.algorithm),
+            Err(SelectionError::InsufficientFunds { .. })
+        ));
+    }
+
+    #[test]
+    fn max_weight() {
+        // Every selector needs several inputs, and only one fits.
+        let pool = build_pool(&[(1_000_000, 0); 3]);
+        let mut rng = StdRng::seed_from_u64(42);
+        let best = select_coins_best(
+            Amount::from_sat(1_500_000),
+            Amount::ZERO,
+            FeeRate::ZERO,
+            Weight::from_wu(160),
+            WASTE,
+            &pool,
+            &mut rng,
+        );
+        assert_eq!(
+            best.map(|best| best.algorithm),
+            Err(SelectionError::MaxWeightExceeded {
+                max_weight: Weight::from_wu(160)
+            })
+        );
+    }
+}
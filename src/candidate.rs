@@ -0,0 +1,319 @@
+//! Preparing a pool for selection.
+//!
+//! Before searching, every algorithm computes the effective value and weight of each
+//! candidate and drops the ones that can't contribute: those that are frozen, those whose
+//! values overflow and those that cost more to spend than they are worth. Dropped
+//! candidates are reported with the reason, so integrators can see why a coin was not
+//! considered. [`CoinSelector`] returns them in the
+//! [rejections](crate::SelectionReport::rejections) of its selections.
+//!
+//! [`CoinSelector`]: crate::CoinSelector
+//!
+//! Dust-heavy pools can additionally be shrunk with a floor on the effective value, see
+//! [`eligible_candidates_with_floor`]. Candidates below it are reported like the others.
+
+use std::fmt;
+
+use bitcoin::{Amount, FeeRate, SignedAmount, Weight};
+
+use crate::WeightedUtxo;
+
+/// A candidate that passed preparation.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Candidate<'a, U> {
+    /// Index of the candidate in the caller's pool.
+    pub index: usize,
+    /// The candidate.
+    pub utxo: &'a U,
+    /// The effective value at the selection fee rate, always positive.
+    pub effective_value: Amount,
+    /// The weight of the input.
+    pub weight: Weight,
+}
+
+impl<U> Clone for Candidate<'_, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<U> Copy for Candidate<'_, U> {}
+
+/// Why a candidate was not considered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RejectReason {
+    /// The candidate is frozen, see [`WeightedUtxo::is_spendable`].
+    Frozen,
+    /// Computing the weight or effective value of the candidate overflowed.
+    Overflow,
+    /// Spending the candidate costs at least as much as its value.
+    NonPositiveEffectiveValue(
+        #[cfg_attr(feature = "serde", serde(with = "bitcoin::amount::serde::as_sat"))] SignedAmount,
+    ),
+    /// The effective value of the candidate is below the floor it was prepared with.
+    BelowFloor {
+        /// The effective value of the candidate.
+        effective_value: Amount,
+        /// The smallest effective value considered.
+        floor: Amount,
+    },
+}
+
+/// A candidate dropped during preparation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rejection {
+    /// Index of the candidate in the caller's pool.
+    pub index: usize,
+    /// Why it was dropped.
+    pub reason: RejectReason,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            RejectReason::Frozen => write!(f, "candidate {}: frozen", self.index),
+            RejectReason::Overflow => write!(f, "candidate {}: value overflows", self.index),
+            RejectReason::NonPositiveEffectiveValue(value) => write!(
+                f,
+                "candidate {}: effective value {} is not positive",
+                self.index, value
+            ),
+            RejectReason::BelowFloor {
+                effective_value,
+                floor,
+            } => write!(
+                f,
+                "candidate {}: effective value {} is below the floor of {}",
+                self.index, effective_value, floor
+            ),
+        }
+    }
+}
+
+/// Returns the candidates of `pool` that can contribute to a selection at `fee_rate`.
+///
+/// Every dropped candidate is passed to `on_reject`. The candidates keep the order of
+/// `pool`.
+pub fn eligible_candidates<U, F>(
+    pool: &[U],
+    fee_rate: FeeRate,
+    on_reject: F,
+) -> Vec<Candidate<'_, U>>
+where
+    U: WeightedUtxo,
+    F: FnMut(Rejection),
+{
+    eligible_candidates_with_floor(pool, fee_rate, Amount::ZERO, on_reject)
+}
+
+/// Returns the candidates of `pool` contributing at least `floor` to a selection at
+/// `fee_rate`.
+///
+/// Like [`eligible_candidates`], but candidates whose effective value is below `floor`
+/// are dropped too, with [`RejectReason::BelowFloor`]. Ignoring candidates that contribute
+/// little shrinks the search space of dust-heavy pools.
+pub fn eligible_candidates_with_floor<U, F>(
+    pool: &[U],
+    fee_rate: FeeRate,
+    floor: Amount,
+    mut on_reject: F,
+) -> Vec<Candidate<'_, U>>
+where
+    U: WeightedUtxo,
+    F: FnMut(Rejection),
+{
+    let mut candidates = Vec::with_capacity(pool.len());
+    for (index, utxo) in pool.iter().enumerate() {
+        match prepare(utxo, fee_rate, floor) {
+            Ok((effective_value, weight)) => candidates.push(Candidate {
+                index,
+                utxo,
+                effective_value,
+                weight,
+            }),
+            Err(reason) => on_reject(Rejection { index, reason }),
+        }
+    }
+    candidates
+}
+
+/// Returns why each dropped candidate of `pool` is not considered at `fee_rate`.
+pub fn rejected_candidates<U: WeightedUtxo>(pool: &[U], fee_rate: FeeRate) -> Vec<Rejection> {
+    rejected_candidates_with_floor(pool, fee_rate, Amount::ZERO)
+}
+
+/// Returns why each dropped candidate of `pool` is not considered at `fee_rate` with an
+/// effective value `floor`, see [`eligible_candidates_with_floor`].
+pub fn rejected_candidates_with_floor<U: WeightedUtxo>(
+    pool: &[U],
+    fee_rate: FeeRate,
+    floor: Amount,
+) -> Vec<Rejection> {
+    let mut rejections = Vec::new();
+    eligible_candidates_with_floor(pool, fee_rate, floor, |rejection| {
+        rejections.push(rejection)
+    });
+    rejections
+}
+
+fn prepare<U: WeightedUtxo>(
+    utxo: &U,
+    fee_rate: FeeRate,
+    floor: Amount,
+) -> Result<(Amount, Weight), RejectReason> {
+    if !utxo.is_spendable() {
+        return Err(RejectReason::Frozen);
+    }
+    let weight = utxo.weight().ok_or(RejectReason::Overflow)?;
+    let effective_value = utxo
+        .effective_value(fee_rate)
+        .ok_or(RejectReason::Overflow)?;
+    if !effective_value.is_positive() {
+        return Err(RejectReason::NonPositiveEffectiveValue(effective_value));
+    }
+    let effective_value = effective_value.unsigned_abs();
+    if effective_value < floor {
+        return Err(RejectReason::BelowFloor {
+            effective_value,
+            floor,
+        });
+    }
+    Ok((effective_value, weight))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_pool;
+
+    #[test]
+    fn eligible_candidates_keep_pool_order() {
+        let pool = build_pool(&[(3_000, 272), (1_000, 272), (2_000, 0)]);
+        let candidates = eligible_candidates(&pool, FeeRate::from_sat_per_vb_u32(1), |_| {
+            panic!("nothing should be rejected")
+        });
+
+        let summary: Vec<_> = candidates
+            .iter()
+            .map(|c| (c.index, c.effective_value.to_sat(), c.weight.to_wu()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(0, 2_892, 432), (1, 892, 432), (2, 1_960, 160)]
+        );
+    }
+
+    #[test]
+    fn rejections_are_reported() {
+        let pool = build_pool(&[
+            (3_000, 272),
+            (1_080, 272),
+            (100, 272),
+            (1, u64::MAX),
+            (u64::MAX, 0),
+        ]);
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+
+        let mut rejections = Vec::new();
+        let candidates = eligible_candidates(&pool, fee_rate, |r| rejections.push(r));
+
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].index, 0);
+        assert_eq!(
+            rejections,
+            vec![
+                Rejection {
+                    index: 1,
+                    reason: RejectReason::NonPositiveEffectiveValue(SignedAmount::ZERO),
+                },
+                Rejection {
+                    index: 2,
+                    reason: RejectReason::NonPositiveEffectiveValue(SignedAmount::from_sat(-980)),
+                },
+                Rejection {
+                    index: 3,
+                    reason: RejectReason::Overflow
+                },
+                Rejection {
+                    index: 4,
+                    reason: RejectReason::Overflow
+                },
+            ]
+        );
+        assert_eq!(rejected_candidates(&pool, fee_rate), rejections);
+    }
+
+    #[test]
+    fn frozen_candidates_are_rejected() {
+        struct Frozen(bool);
+        impl WeightedUtxo for Frozen {
+            fn satisfaction_weight(&self) -> Weight {
+                Weight::ZERO
+            }
+            fn value(&self) -> Amount {
+                Amount::from_sat(1_000)
+            }
+            fn is_spendable(&self) -> bool {
+                !self.0
+            }
+        }
+
+        let pool = [Frozen(true), Frozen(false), Frozen(true)];
+        let mut rejections = Vec::new();
+        let candidates = eligible_candidates(&pool, FeeRate::ZERO, |r| rejections.push(r));
+
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].index, 1);
+        let frozen: Vec<_> = rejections
+            .iter()
+            .filter(|r| r.reason == RejectReason::Frozen)
+            .map(|r| r.index)
+            .collect();
+        assert_eq!(frozen, vec![0, 2]);
+        assert_eq!(rejections[0].to_string(), "candidate 0: frozen");
+    }
+
+    #[test]
+    fn floor_rejects_small_contributions() {
+        let pool = build_pool(&[(3_000, 272), (1_500, 272), (1_000, 272)]);
+        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+        let floor = Amount::from_sat(1_000);
+
+        let candidates: Vec<_> = eligible_candidates_with_floor(&pool, fee_rate, floor, |_| {})
+            .iter()
+            .map(|c| c.index)
+            .collect();
+        assert_eq!(candidates, vec![0, 1]);
+
+        let rejections = rejected_candidates_with_floor(&pool, fee_rate, floor);
+        assert_eq!(
+            rejections,
+            vec![Rejection {
+                index: 2,
+                reason: RejectReason::BelowFloor {
+                    effective_value: Amount::from_sat(892),
+                    floor,
+                },
+            }]
+        );
+        assert_eq!(
+            rejections[0].to_string(),
+            "candidate 2: effective value 0.00000892 BTC is below the floor of 0.00001000 BTC"
+        );
+    }
+
+    #[test]
+    fn rejection_display() {
+        let rejection = Rejection {
+            index: 2,
+            reason: RejectReason::NonPositiveEffectiveValue(SignedAmount::from_sat(-980)),
+        };
+        assert_eq!(
+            rejection.to_string(),
+            "candidate 2: effective value -0.0000098 BTC is not positive"
+        );
+    }
+}
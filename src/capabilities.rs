@@ -0,0 +1,61 @@
+//! What this build of the crate can do.
+//!
+//! Frameworks wrapping the crate can ask [`capabilities`] at runtime which algorithms and
+//! optional features are available, instead of mirroring the crate's feature flags.
+
+/// A selection algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// [`select_coins_coin_grinder`](crate::coin_grinder::select_coins_coin_grinder).
+    CoinGrinder,
+    /// [`select_coins_consolidate`](crate::consolidate::select_coins_consolidate).
+    Consolidate,
+    /// [`select_coins_knapsack`](crate::knapsack::select_coins_knapsack).
+    Knapsack,
+    /// [`select_coins_prefix_sum`](crate::prefix_sum::select_coins_prefix_sum).
+    PrefixSum,
+    /// [`select_coins_srd`](crate::srd::select_coins_srd).
+    SingleRandomDraw,
+}
+
+/// The algorithms and optional features compiled in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The version of the crate, which versions its behavior too.
+    pub version: &'static str,
+    /// The available selection algorithms.
+    pub algorithms: &'static [Algorithm],
+    /// Whether linkability analysis is available, with the `linkability` feature.
+    pub linkability: bool,
+    /// Whether the owned types implement serde's traits, with the `serde` feature.
+    pub serde: bool,
+}
+
+/// Returns what this build of the crate can do.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        algorithms: &[
+            Algorithm::CoinGrinder,
+            Algorithm::Consolidate,
+            Algorithm::Knapsack,
+            Algorithm::PrefixSum,
+            Algorithm::SingleRandomDraw,
+        ],
+        linkability: cfg!(feature = "linkability"),
+        serde: cfg!(feature = "serde"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_build() {
+        let capabilities = capabilities();
+        assert!(capabilities.algorithms.contains(&Algorithm::Knapsack));
+        assert_eq!(capabilities.linkability, cfg!(feature = "linkability"));
+        assert_eq!(capabilities.serde, cfg!(feature = "serde"));
+    }
+}
@@ -0,0 +1,319 @@
+//! Rounding change to a denomination ladder.
+//!
+//! Change of an arbitrary value is unlikely to ever match a future payment exactly.
+//! Rounding it down to one of a few configured denominations, and paying the difference as
+//! fee, makes changeless spends of it far more likely later. The difference is an extra
+//! cost of the selection, which [`DenominationLadder::excess`] reports so selectors can
+//! take it into account when comparing solutions. [`CoinSelector::change_denominations`]
+//! applies a ladder during selection.
+//!
+//! [`CoinSelector::change_denominations`]: crate::CoinSelector::change_denominations
+//!
+//! [`ChangeHistory`] learns the other change parameters from the wallet's past
+//! transactions: the value change is worth creating and what a change output costs, which
+//! integrators otherwise have to guess.
+//!
+//! [`ChangeOutput`] derives the fee and cost of change from the change script, like
+//! Bitcoin Core's `CoinSelectionParams`, so callers don't have to work them out.
+
+use bitcoin::{Amount, FeeRate, Script, TxOut, Weight};
+
+use crate::defaults::TX_IN_BASE_WEIGHT;
+use crate::weight::{infer_satisfaction_weight, UnsupportedScriptError};
+
+/// Bitcoin Core's default discard fee rate, `-discardfee`, the rate change is assumed to
+/// be spent at later.
+pub const DISCARD_FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_u32(10);
+
+/// A set of denominations change is rounded down to.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DenominationLadder {
+    denominations: Vec<Amount>,
+}
+
+impl DenominationLadder {
+    /// Creates a ladder from `denominations`, in any order.
+    ///
+    /// Duplicates and zero denominations are ignored.
+    pub fn new<I: IntoIterator<Item = Amount>>(denominations: I) -> Self {
+        let mut denominations: Vec<Amount> = denominations
+            .into_iter()
+            .filter(|&d| d > Amount::ZERO)
+            .collect();
+        denominations.sort_unstable();
+        denominations.dedup();
+        DenominationLadder { denominations }
+    }
+
+    /// The denominations of the ladder, in ascending order.
+    pub fn denominations(&self) -> &[Amount] {
+        &self.denominations
+    }
+
+    /// Rounds `change` down to the largest denomination not exceeding it.
+    ///
+    /// Returns `None` if `change` is below the smallest denomination, in which case the
+    /// whole change goes to fees. The caller must still check the result against the
+    /// dust threshold of the change output.
+    pub fn round_down(&self, change: Amount) -> Option<Amount> {
+        let rung = self.denominations.partition_point(|&d| d <= change);
+        rung.checked_sub(1).map(|rung| self.denominations[rung])
+    }
+
+    /// The part of `change` paid as fee by rounding it down.
+    pub fn excess(&self, change: Amount) -> Amount {
+        change - self.round_down(change).unwrap_or(Amount::ZERO)
+    }
+}
+
+/// The change outputs a wallet created in the past.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ChangeHistory {
+    values: Vec<Amount>,
+    spend_fee_rates: Vec<FeeRate>,
+}
+
+impl ChangeHistory {
+    /// Creates an empty history.
+    pub fn new() -> Self {
+        ChangeHistory::default()
+    }
+
+    /// Records a change output of `value`, spent at `spent_at` if it was spent.
+    pub fn record(&mut self, value: Amount, spent_at: Option<FeeRate>) {
+        self.values.push(value);
+        self.spend_fee_rates.extend(spent_at);
+    }
+
+    /// The value below which `percentile` percent of the recorded change falls.
+    ///
+    /// Uses the nearest rank, so the result is always a recorded value. Percentiles above
+    /// 100 are treated as 100. Returns `None` if no change was recorded.
+    pub fn change_target(&self, percentile: u8) -> Option<Amount> {
+        let mut values = self.values.clone();
+        values.sort_unstable();
+        nearest_rank(&values, percentile)
+    }
+
+    /// The median fee rate recorded change was spent at.
+    ///
+    /// This is the rate the wallet actually pays to spend change, and can stand in for the
+    /// long-term fee rate. Returns `None` if no spend was recorded.
+    pub fn spend_fee_rate(&self) -> Option<FeeRate> {
+        let mut fee_rates = self.spend_fee_rates.clone();
+        fee_rates.sort_unstable();
+        nearest_rank(&fee_rates, 50)
+    }
+
+    /// The cost of creating a change output of `output_weight` at `fee_rate` and spending
+    /// it later as an input of `input_weight` at [`spend_fee_rate`](Self::spend_fee_rate).
+    ///
+    /// Returns `None` if no spend was recorded or on overflow.
+    pub fn cost_of_change(
+        &self,
+        fee_rate: FeeRate,
+        output_weight: Weight,
+        input_weight: Weight,
+    ) -> Option<Amount> {
+        let creation = fee_rate.fee_wu(output_weight)?;
+        let spend = self.spend_fee_rate()?.fee_wu(input_weight)?;
+        creation.checked_add(spend)
+    }
+}
+
+/// The change output of a transaction, described by the weights of creating and spending
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeOutput {
+    output_weight: Weight,
+    spend_weight: Weight,
+}
+
+impl ChangeOutput {
+    /// Describes a change output weighing `output_weight`, spent later by an input with
+    /// `satisfaction_weight`.
+    ///
+    /// Returns `None` if the weight of the spending input overflows.
+    pub fn new(output_weight: Weight, satisfaction_weight: Weight) -> Option<Self> {
+        Some(ChangeOutput {
+            output_weight,
+            spend_weight: TX_IN_BASE_WEIGHT.checked_add(satisfaction_weight)?,
+        })
+    }
+
+    /// Describes a change output paying to `script_pubkey`.
+    ///
+    /// The satisfaction weight is inferred as [`infer_satisfaction_weight`] does, which
+    /// fails for script types whose spend the script doesn't determine.
+    pub fn from_script(script_pubkey: &Script) -> Result<Self, UnsupportedScriptError> {
+        let output = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: script_pubkey.to_owned(),
+        };
+        let satisfaction_weight = infer_satisfaction_weight(script_pubkey)?;
+        ChangeOutput::new(output.weight(), satisfaction_weight).ok_or(UnsupportedScriptError)
+    }
+
+    /// The weight of the change output.
+    pub fn output_weight(&self) -> Weight {
+        self.output_weight
+    }
+
+    /// The weight of the input spending the change output.
+    pub fn spend_weight(&self) -> Weight {
+        self.spend_weight
+    }
+
+    /// The fee for creating the change output at `fee_rate`, Bitcoin Core's `m_change_fee`.
+    ///
+    /// Returns `None` on overflow.
+    pub fn change_fee(&self, fee_rate: FeeRate) -> Option<Amount> {
+        fee_rate.fee_wu(self.output_weight)
+    }
+
+    /// The cost of creating the change output at `fee_rate` and spending it later at
+    /// `discard_fee_rate`, Bitcoin Core's `m_cost_of_change`.
+    ///
+    /// [`DISCARD_FEE_RATE`] is Bitcoin Core's default discard fee rate. Returns `None` on
+    /// overflow.
+    pub fn cost_of_change(&self, fee_rate: FeeRate, discard_fee_rate: FeeRate) -> Option<Amount> {
+        self.change_fee(fee_rate)?
+            .checked_add(discard_fee_rate.fee_wu(self.spend_weight)?)
+    }
+}
+
+fn nearest_rank<T: Copy>(sorted: &[T], percentile: u8) -> Option<T> {
+    let percentile = usize::from(percentile.min(100));
+    let rank = (percentile * sorted.len()).div_ceil(100);
+    sorted.get(rank.saturating_sub(1)).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ladder() -> DenominationLadder {
+        DenominationLadder::new(
+            [100_000, 10_000, 0, 50_000, 10_000]
+                .iter()
+                .map(|&sat| Amount::from_sat(sat)),
+        )
+    }
+
+    #[test]
+    fn denominations_are_normalized() {
+        let denominations: Vec<u64> = ladder()
+            .denominations()
+            .iter()
+            .map(|d| d.to_sat())
+            .collect();
+        assert_eq!(denominations, vec![10_000, 50_000, 100_000]);
+    }
+
+    #[test]
+    fn round_down() {
+        let ladder = ladder();
+        assert_eq!(ladder.round_down(Amount::from_sat(9_999)), None);
+        assert_eq!(
+            ladder.round_down(Amount::from_sat(10_000)),
+            Some(Amount::from_sat(10_000))
+        );
+        assert_eq!(
+            ladder.round_down(Amount::from_sat(73_210)),
+            Some(Amount::from_sat(50_000))
+        );
+        assert_eq!(
+            ladder.round_down(Amount::MAX),
+            Some(Amount::from_sat(100_000))
+        );
+        assert_eq!(DenominationLadder::default().round_down(Amount::MAX), None);
+    }
+
+    #[test]
+    fn excess() {
+        let ladder = ladder();
+        assert_eq!(
+            ladder.excess(Amount::from_sat(73_210)),
+            Amount::from_sat(23_210)
+        );
+        assert_eq!(ladder.excess(Amount::from_sat(50_000)), Amount::ZERO);
+        assert_eq!(
+            ladder.excess(Amount::from_sat(9_999)),
+            Amount::from_sat(9_999)
+        );
+    }
+
+    fn history() -> ChangeHistory {
+        let mut history = ChangeHistory::new();
+        for (value, spent_at) in [
+            (40_000, Some(5)),
+            (10_000, None),
+            (30_000, Some(20)),
+            (20_000, Some(2)),
+            (50_000, None),
+        ] {
+            history.record(
+                Amount::from_sat(value),
+                spent_at.map(FeeRate::from_sat_per_vb_u32),
+            );
+        }
+        history
+    }
+
+    #[test]
+    fn change_target_percentiles() {
+        let history = history();
+        assert_eq!(history.change_target(0), Some(Amount::from_sat(10_000)));
+        assert_eq!(history.change_target(20), Some(Amount::from_sat(10_000)));
+        assert_eq!(history.change_target(50), Some(Amount::from_sat(30_000)));
+        assert_eq!(history.change_target(100), Some(Amount::from_sat(50_000)));
+        assert_eq!(history.change_target(255), Some(Amount::from_sat(50_000)));
+        assert_eq!(ChangeHistory::new().change_target(50), None);
+    }
+
+    #[test]
+    fn cost_of_change() {
+        let history = history();
+        assert_eq!(
+            history.spend_fee_rate(),
+            Some(FeeRate::from_sat_per_vb_u32(5))
+        );
+
+        // A P2WPKH output is 124 wu and spending it takes 272 wu.
+        let cost = history.cost_of_change(
+            FeeRate::from_sat_per_vb_u32(10),
+            Weight::from_wu(124),
+            Weight::from_wu(272),
+        );
+        assert_eq!(cost, Some(Amount::from_sat(310 + 340)));
+        assert_eq!(
+            ChangeHistory::new().cost_of_change(FeeRate::ZERO, Weight::ZERO, Weight::ZERO),
+            None
+        );
+    }
+
+    #[test]
+    fn change_output() {
+        use bitcoin::hashes::Hash;
+        use bitcoin::{ScriptBuf, WPubkeyHash};
+
+        // A P2WPKH output is 124 wu and spending it takes 160 + 112 wu.
+        let p2wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        let change = ChangeOutput::from_script(&p2wpkh).unwrap();
+        assert_eq!(change.output_weight(), Weight::from_wu(124));
+        assert_eq!(change.spend_weight(), Weight::from_wu(272));
+
+        let fee_rate = FeeRate::from_sat_per_vb_u32(20);
+        assert_eq!(change.change_fee(fee_rate), Some(Amount::from_sat(620)));
+        assert_eq!(
+            change.cost_of_change(fee_rate, DISCARD_FEE_RATE),
+            Some(Amount::from_sat(620 + 680))
+        );
+
+        assert_eq!(
+            ChangeOutput::from_script(&ScriptBuf::new()),
+            Err(UnsupportedScriptError)
+        );
+        assert_eq!(ChangeOutput::new(Weight::ZERO, Weight::MAX), None);
+    }
+}
@@ -0,0 +1,483 @@
+//! CoinGrinder.
+//!
+//! At high fee rates every weight unit is expensive, so Bitcoin Core searches for the
+//! lightest input set that funds the payment and a change output. CoinGrinder is a depth
+//! first search over the candidates sorted by descending effective value, which skips
+//! branches that can't reach the target, can't beat the lightest solution found so far,
+//! or only differ from an explored branch by swapping equivalent candidates.
+
+use core::convert::TryFrom;
+
+use bitcoin::{Amount, FeeRate, Weight};
+
+use crate::candidate::eligible_candidates;
+use crate::order::sort_candidates;
+use crate::sample::{stratified_sample, SAMPLE_SIZE, SAMPLE_THRESHOLD};
+use crate::target::check_target;
+use crate::{SelectionError, SelectionReport, WeightedUtxo};
+
+/// Number of selections tried before giving up, as in Bitcoin Core.
+pub const ITERATION_LIMIT: usize = 100_000;
+
+/// What a search does when it reaches its iteration limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LimitPolicy {
+    /// Return the best selection found so far, flagged as
+    /// [`limit_hit`](SelectionReport::limit_hit).
+    #[default]
+    ReturnBest,
+    /// Return [`SelectionError::IterationLimitReached`], even if a selection was found.
+    ReturnError,
+}
+
+/// Selects the lightest candidates covering `target` plus `change_target`.
+///
+/// Effective values at `fee_rate` are used. The selection must leave at least
+/// `change_target` for the change output, its inputs may weigh at most `max_weight` and
+/// there may be at most `max_input_count` of them.
+/// Among selections of equal weight the one with the smallest effective value is chosen.
+///
+/// If the iteration limit is reached, `on_limit` decides whether the lightest selection
+/// found so far is returned, with the report flagging it, or an error.
+/// Pools with more than [`SAMPLE_THRESHOLD`] eligible candidates are too large to search
+/// and only a [stratified sample](stratified_sample) of them is, which the report flags.
+/// If the sample can't cover the target and change target, the whole pool is searched.
+/// The selection is in canonical order, its excess over `target` is the change, and the
+/// report counts the selections tried and records the change target.
+pub fn select_coins_coin_grinder<U: WeightedUtxo>(
+    target: Amount,
+    change_target: Amount,
+    max_weight: Weight,
+    max_input_count: usize,
+    fee_rate: FeeRate,
+    on_limit: LimitPolicy,
+    pool: &[U],
+) -> Result<SelectionReport<'_, U>, SelectionError> {
+    check_target(target)?;
+    let total_target = target
+        .checked_add(change_target)
+        .ok_or(SelectionError::Overflow)?;
+    let required = total_target;
+    // Sums are kept in 128 bits, which no pool can overflow.
+    let total_target = u128::from(total_target.to_sat());
+    let max_weight = u128::from(max_weight.to_wu());
+
+    let mut candidates = eligible_candidates(pool, fee_rate, |_| {});
+    sort_candidates(&mut candidates);
+    let mut sampled = false;
+    if candidates.len() > SAMPLE_THRESHOLD {
+        let sample = stratified_sample(candidates.clone(), SAMPLE_SIZE);
+        // A sample too small to fund the selection doesn't say the pool is.
+        let sample_value: u128 = sample
+            .iter()
+            .map(|c| u128::from(c.effective_value.to_sat()))
+            .sum();
+        if sample_value >= total_target {
+            candidates = sample;
+            sampled = true;
+        }
+    }
+    let values: Vec<u128> = candidates
+        .iter()
+        .map(|c| u128::from(c.effective_value.to_sat()))
+        .collect();
+    let weights: Vec<u128> = candidates
+        .iter()
+        .map(|c| u128::from(c.weight.to_wu()))
+        .collect();
+
+    // The effective value and the lowest weight of the candidates after each one.
+    let mut lookahead = vec![0; values.len()];
+    let mut min_tail_weight = vec![u128::MAX; values.len()];
+    for i in (1..values.len()).rev() {
+        lookahead[i - 1] = lookahead[i] + values[i];
+        min_tail_weight[i - 1] = min_tail_weight[i].min(weights[i]);
+    }
+    let available = values.first().map_or(0, |&first| first + lookahead[0]);
+    let insufficient_funds = SelectionError::InsufficientFunds {
+        available: Amount::from_sat(u64::try_from(available).unwrap_or(u64::MAX)),
+        required,
+    };
+    if available < total_target {
+        return Err(insufficient_funds);
+    }
+    if max_input_count == 0 {
+        return Err(SelectionError::MaxInputCountExceeded { max_input_count });
+    }
+
+    let mut selection: Vec<usize> = Vec::new();
+    let mut amount: u128 = 0;
+    let mut weight: u128 = 0;
+    let mut best: Option<Vec<usize>> = None;
+    let mut best_amount = u128::MAX;
+    let mut best_weight = max_weight;
+    let mut max_weight_exceeded = false;
+    let mut max_input_count_exceeded = false;
+
+    let mut next = 0;
+    let mut iterations = 0;
+    let mut limit_hit = false;
+    loop {
+        // Explore: add the next candidate.
+        selection.push(next);
+        amount += values[next];
+        weight += weights[next];
+        iterations += 1;
+        let last = next;
+        next += 1;
+
+        let mut should_cut = false;
+        let mut should_shift = false;
+        if amount + lookahead[last] < total_target {
+            // Even all remaining candidates can't reach the target.
+            should_cut = true;
+        } else if weight > best_weight {
+            if best.is_none() {
+                max_weight_exceeded = true;
+            }
+            // Later candidates are smaller, so a lighter one may still do.
+            if weights[last] <= min_tail_weight[last] {
+                should_cut = true;
+            } else {
+                should_shift = true;
+            }
+        } else if amount >= total_target {
+            should_shift = true;
+            if weight < best_weight || (weight == best_weight && amount < best_amount) {
+                best = Some(selection.clone());
+                best_amount = amount;
+                best_weight = weight;
+            }
+        } else if selection.len() >= max_input_count {
+            // Later candidates are smaller, so no other one reaches the target either.
+            max_input_count_exceeded = true;
+            should_cut = true;
+        } else if best.is_some() {
+            // Each further candidate adds at most the value of the last one at no less than
+            // the lowest remaining weight.
+            let missing = (total_target - amount).div_ceil(values[last]);
+            let min_weight = weight.saturating_add(min_tail_weight[last].saturating_mul(missing));
+            if min_weight > best_weight {
+                if weights[last] <= min_tail_weight[last] {
+                    should_cut = true;
+                } else {
+                    should_shift = true;
+                }
+            }
+        }
+
+        if iterations >= ITERATION_LIMIT {
+            limit_hit = true;
+            break;
+        }
+        if next == values.len() {
+            should_cut = true;
+        }
+
+        if should_cut {
+            // Nothing after the last candidate helps: drop it and shift the one before.
+            deselect(&mut selection, &mut amount, &mut weight, &values, &weights);
+            should_shift = true;
+        }
+        let mut exhausted = false;
+        while should_shift {
+            let Some(&last) = selection.last() else {
+                exhausted = true;
+                break;
+            };
+            // Shift: replace the last candidate with the one after it.
+            next = last + 1;
+            deselect(&mut selection, &mut amount, &mut weight, &values, &weights);
+            should_shift = false;
+            // An unselected candidate equivalent to the previous one leads to the same
+            // selections, so skip it.
+            while values[next - 1] == values[next] && weights[next - 1] == weights[next] {
+                if next >= values.len() - 1 {
+                    should_shift = true;
+                    break;
+                }
+                next += 1;
+            }
+        }
+        if exhausted {
+            break;
+        }
+    }
+
+    match best {
+        _ if limit_hit && on_limit == LimitPolicy::ReturnError => {
+            Err(SelectionError::IterationLimitReached)
+        }
+        Some(best) => {
+            let selection = best.into_iter().map(|i| candidates[i]).collect();
+            let mut report = SelectionReport::new(selection, target, iterations)?;
+            report.sampled = sampled;
+            report.limit_hit = limit_hit;
+            report.change_target = Some(change_target);
+            Ok(report)
+        }
+        None if limit_hit => Err(SelectionError::IterationLimitReached),
+        None if max_weight_exceeded => Err(SelectionError::MaxWeightExceeded {
+            max_weight: Weight::from_wu(max_weight as u64),
+        }),
+        None if max_input_count_exceeded => {
+            Err(SelectionError::MaxInputCountExceeded { max_input_count })
+        }
+        None => Err(insufficient_funds),
+    }
+}
+
+fn deselect(
+    selection: &mut Vec<usize>,
+    amount: &mut u128,
+    weight: &mut u128,
+    values: &[u128],
+    weights: &[u128],
+) {
+    if let Some(last) = selection.pop() {
+        *amount -= values[last];
+        *weight -= weights[last];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_pool;
+
+    const MAX_WEIGHT: Weight = Weight::from_wu(400_000);
+
+    fn select(
+        target: u64,
+        change_target: u64,
+        max_weight: Weight,
+        pool: &[(u64, u64)],
+    ) -> Result<Vec<usize>, SelectionError> {
+        let pool = build_pool(pool);
+        select_coins_coin_grinder(
+            Amount::from_sat(target),
+            Amount::from_sat(change_target),
+            max_weight,
+            usize::MAX,
+            FeeRate::ZERO,
+            LimitPolicy::ReturnBest,
+            &pool,
+        )
+        .map(|report| report.selection.iter().map(|c| c.index).collect())
+    }
+
+    #[test]
+    fn lightest_selection() {
+        // Two heavy inputs reach the target, one light input plus a heavy one is lighter.
+        let pool = [(6_000, 500), (5_000, 500), (4_000, 0), (1_000, 500)];
+        assert_eq!(select(9_000, 500, MAX_WEIGHT, &pool), Ok(vec![0, 2]));
+    }
+
+    #[test]
+    fn fewer_inputs_beat_larger_amounts() {
+        let pool = [(10_000, 112), (3_000, 112), (3_000, 112), (3_000, 112)];
+        assert_eq!(select(8_000, 1_000, MAX_WEIGHT, &pool), Ok(vec![0]));
+    }
+
+    #[test]
+    fn smaller_amount_on_equal_weight() {
+        let pool = [(10_000, 112), (7_000, 112), (3_000, 112)];
+        assert_eq!(select(6_000, 500, MAX_WEIGHT, &pool), Ok(vec![1]));
+    }
+
+    #[test]
+    fn clones_are_skipped() {
+        // Many equivalent candidates, only a few needed.
+        let pool = [(1_000, 272); 200];
+        let selection = select(2_500, 0, MAX_WEIGHT, &pool).unwrap();
+        assert_eq!(selection, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn change_target_is_required() {
+        let pool = [(5_000, 0), (4_000, 0)];
+        assert_eq!(select(5_000, 0, MAX_WEIGHT, &pool), Ok(vec![0]));
+        assert_eq!(select(5_000, 1, MAX_WEIGHT, &pool), Ok(vec![0, 1]));
+    }
+
+    #[test]
+    fn max_input_count() {
+        // Three light inputs are lightest, but the heavy one is needed with two.
+        let pool = build_pool(&[(10_000, 1_000), (4_000, 0), (4_000, 0), (4_000, 0)]);
+        let select = |max_input_count| {
+            select_coins_coin_grinder(
+                Amount::from_sat(11_000),
+                Amount::ZERO,
+                MAX_WEIGHT,
+                max_input_count,
+                FeeRate::ZERO,
+                LimitPolicy::ReturnBest,
+                &pool,
+            )
+            .map(|report| report.selection.iter().map(|c| c.index).collect::<Vec<_>>())
+        };
+        assert_eq!(select(3), Ok(vec![1, 2, 3]));
+        assert_eq!(select(2), Ok(vec![0, 1]));
+        assert_eq!(
+            select(1),
+            Err(SelectionError::MaxInputCountExceeded { max_input_count: 1 })
+        );
+    }
+
+    #[test]
+    fn max_weight() {
+        let pool = [(5_000, 112), (4_000, 112)];
+        assert_eq!(
+            select(8_000, 0, Weight::from_wu(543), &pool),
+            Err(SelectionError::MaxWeightExceeded {
+                max_weight: Weight::from_wu(543)
+            })
+        );
+        assert_eq!(
+            select(8_000, 0, Weight::from_wu(544), &pool),
+            Ok(vec![0, 1])
+        );
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        let pool = [(5_000, 0), (4_000, 0)];
+        assert_eq!(
+            select(8_000, 1_001, MAX_WEIGHT, &pool),
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(9_000),
+                required: Amount::from_sat(9_001),
+            })
+        );
+    }
+
+    #[test]
+    fn matches_exhaustive_search() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..200 {
+            let size = rng.gen_range(1..10);
+            let pool: Vec<(u64, u64)> = (0..size)
+                .map(|_| (rng.gen_range(1..20) * 1_000, rng.gen_range(0..4) * 100))
+                .collect();
+            let target = rng.gen_range(1..60) * 1_000;
+            let change_target = rng.gen_range(0..3) * 500;
+
+            // The lightest subset covering the target, then the one with the least value.
+            let best = (1..1u32 << size)
+                .map(|mask| {
+                    let (value, weight) = (0..size)
+                        .filter(|i| mask & (1 << i) != 0)
+                        .fold((0, 0), |(v, w), i| (v + pool[i].0, w + pool[i].1 + 160));
+                    (weight, value)
+                })
+                .filter(|&(_, value)| value >= target + change_target)
+                .min();
+
+            let utxos = build_pool(&pool);
+            let report = select_coins_coin_grinder(
+                Amount::from_sat(target),
+                Amount::from_sat(change_target),
+                MAX_WEIGHT,
+                usize::MAX,
+                FeeRate::ZERO,
+                LimitPolicy::ReturnBest,
+                &utxos,
+            );
+            let found = report
+                .ok()
+                .map(|r| (r.weight.to_wu(), r.effective_value.to_sat()));
+            assert_eq!(found, best, "pool {:?} target {}", pool, target);
+        }
+    }
+
+    #[test]
+    fn huge_values() {
+        let huge = i64::MAX as u64;
+        let pool = [(huge, 0), (huge, 0), (huge, 0), (1, 0)];
+        assert_eq!(select(huge, 1, MAX_WEIGHT, &pool), Ok(vec![0, 3]));
+    }
+
+    #[test]
+    fn large_pools_are_sampled() {
+        let small = build_pool(&[(5_000, 272); 10]);
+        let report = select_coins_coin_grinder(
+            Amount::from_sat(12_000),
+            Amount::ZERO,
+            MAX_WEIGHT,
+            usize::MAX,
+            FeeRate::ZERO,
+            LimitPolicy::ReturnBest,
+            &small,
+        )
+        .unwrap();
+        assert!(!report.sampled);
+
+        let mut pool = vec![(1_000, 272); SAMPLE_THRESHOLD];
+        pool.push((50_000, 272));
+        let pool = build_pool(&pool);
+        let report = select_coins_coin_grinder(
+            Amount::from_sat(52_000),
+            Amount::ZERO,
+            MAX_WEIGHT,
+            usize::MAX,
+            FeeRate::ZERO,
+            LimitPolicy::ReturnBest,
+            &pool,
+        )
+        .unwrap();
+        assert!(report.sampled);
+        let indices: Vec<usize> = report.selection.iter().map(|c| c.index).collect();
+        assert_eq!(indices, vec![SAMPLE_THRESHOLD, 0, 1]);
+    }
+
+    #[test]
+    fn short_samples_fall_back_to_the_pool() {
+        // The pool is worth over 5 billion sats, a sample of it less than 1 billion.
+        let pool: Vec<(u64, u64)> = (0..=SAMPLE_THRESHOLD as u64)
+            .map(|i| (1_000 + i, 0))
+            .collect();
+        let pool = build_pool(&pool);
+        let target = Amount::from_sat(2_000_000_000);
+        let report = select_coins_coin_grinder(
+            target,
+            Amount::ZERO,
+            Weight::MAX,
+            usize::MAX,
+            FeeRate::ZERO,
+            LimitPolicy::ReturnBest,
+            &pool,
+        )
+        .unwrap();
+        assert!(!report.sampled);
+        assert!(report.effective_value >= target);
+    }
+
+    #[test]
+    fn iteration_limit() {
+        // Distinct values with alternating weights leave little to prune.
+        let pool: Vec<(u64, u64)> = (0..60).map(|i| (1_000_000 + i * 7, 272 + i % 3)).collect();
+        let pool = build_pool(&pool);
+        let select = |on_limit| {
+            select_coins_coin_grinder(
+                Amount::from_sat(30_000_000),
+                Amount::ZERO,
+                MAX_WEIGHT,
+                usize::MAX,
+                FeeRate::ZERO,
+                on_limit,
+                &pool,
+            )
+        };
+
+        let report = select(LimitPolicy::ReturnBest).unwrap();
+        assert_eq!(report.iterations, ITERATION_LIMIT);
+        assert!(report.limit_hit);
+        assert_eq!(
+            select(LimitPolicy::ReturnError).map(|_| ()),
+            Err(SelectionError::IterationLimitReached)
+        );
+    }
+}
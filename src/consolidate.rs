@@ -0,0 +1,90 @@
+//! Consolidating selection.
+//!
+//! When fees are low, or when the funds stay in the wallet anyway, spending many small
+//! candidates now is cheaper than spending them later at a higher fee rate. This selector
+//! takes the smallest candidates first, shrinking the pool as a side effect.
+
+use bitcoin::{Amount, FeeRate};
+
+use crate::candidate::eligible_candidates;
+use crate::order::sort_candidates;
+use crate::target::check_target;
+use crate::{SelectionError, SelectionReport, WeightedUtxo};
+
+/// Selects candidates by ascending effective value at `fee_rate` until they cover `target`.
+///
+/// Ties are broken by descending weight, so the heavier of two equally valued candidates
+/// leaves the pool first, then by pool index.
+///
+/// The selection is in canonical order and the report counts no iterations.
+pub fn select_coins_consolidate<U: WeightedUtxo>(
+    target: Amount,
+    fee_rate: FeeRate,
+    pool: &[U],
+) -> Result<SelectionReport<'_, U>, SelectionError> {
+    check_target(target)?;
+    let mut candidates = eligible_candidates(pool, fee_rate, |_| {});
+    sort_candidates(&mut candidates);
+    candidates.reverse();
+
+    let mut selection = Vec::new();
+    let mut available = Amount::ZERO;
+    for candidate in candidates {
+        if available >= target {
+            break;
+        }
+        available = available
+            .checked_add(candidate.effective_value)
+            .ok_or(SelectionError::Overflow)?;
+        selection.push(candidate);
+    }
+
+    sort_candidates(&mut selection);
+    SelectionReport::new(selection, target, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_pool;
+
+    fn select(target: u64, pool: &[(u64, u64)]) -> Result<Vec<usize>, SelectionError> {
+        let pool = build_pool(pool);
+        select_coins_consolidate(Amount::from_sat(target), FeeRate::ZERO, &pool)
+            .map(|report| report.selection.iter().map(|c| c.index).collect())
+    }
+
+    #[test]
+    fn smallest_first() {
+        let pool = [
+            (50_000, 0),
+            (1_000, 0),
+            (3_000, 0),
+            (2_000, 272),
+            (2_000, 0),
+        ];
+        assert_eq!(select(4_000, &pool), Ok(vec![4, 3, 1]));
+        assert_eq!(select(9_000, &pool), Ok(vec![0, 2, 4, 3, 1]));
+    }
+
+    #[test]
+    fn zero_target() {
+        assert_eq!(
+            select(0, &[(1_000, 0)]),
+            Err(SelectionError::InvalidTarget(
+                crate::target::InvalidTargetError::Zero
+            ))
+        );
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        assert_eq!(
+            select(5_000, &[(1_000, 0), (3_000, 0)]),
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(4_000),
+                required: Amount::from_sat(5_000),
+            })
+        );
+    }
+}
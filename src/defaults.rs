@@ -0,0 +1,71 @@
+//! Every tunable number of the crate in one place.
+//!
+//! Constants live next to the code they tune, which makes them hard to audit together.
+//! This module collects them, either defined here and used throughout the crate or
+//! re-exported from their module, so integrators can inspect them and override the
+//! corresponding parameters coherently. Where a parameter can be set, the constant is
+//! its default.
+
+use bitcoin::{Amount, FeeRate, Weight};
+
+pub use crate::change::DISCARD_FEE_RATE;
+pub use crate::coin_grinder::ITERATION_LIMIT as COIN_GRINDER_ITERATION_LIMIT;
+pub use crate::group::MAX_GROUP_SIZE;
+pub use crate::knapsack::{DEFAULT_MIN_CHANGE, ITERATIONS as KNAPSACK_ITERATIONS};
+#[cfg(feature = "linkability")]
+pub use crate::linkability::MAX_SUBSET_INPUTS;
+pub use crate::maturity::COINBASE_MATURITY;
+pub use crate::privacy::ROUND_CHANGE_GRANULARITY;
+pub use crate::sample::{SAMPLE_SIZE, SAMPLE_THRESHOLD};
+pub use crate::srd::{CHANGE_LOWER, CHANGE_UPPER};
+pub use crate::weight::CHANGE_SCRIPT_LEN;
+
+/// Weight of the parts of an input that do not depend on the script being spent: the
+/// outpoint (36 bytes) and the sequence (4 bytes). Every input weighs this plus its
+/// satisfaction weight.
+pub const TX_IN_BASE_WEIGHT: Weight = Weight::from_wu(160);
+
+/// Bitcoin Core's `MAX_STANDARD_TX_WEIGHT`, the heaviest transaction relayed.
+pub const MAX_STANDARD_TX_WEIGHT: Weight = Weight::from_wu(400_000);
+
+/// The most inputs whose count is encoded in a single byte.
+pub const MAX_SINGLE_BYTE_INPUTS: usize = 252;
+
+/// Bitcoin Core's default dust relay fee rate, `-dustrelayfee`.
+pub const DUST_RELAY_FEE: FeeRate = FeeRate::from_sat_per_vb_u32(3);
+
+/// The dust threshold of a P2PKH output at [`DUST_RELAY_FEE`], the highest of the common
+/// output types.
+pub const DUST_LIMIT: Amount = Amount::from_sat(546);
+
+/// More identical candidates than this are flagged by the pool lint.
+pub const MAX_DUPLICATES: usize = 100;
+
+/// Fee rates above this minimize weight, see [`AutoPolicy`](crate::policy::AutoPolicy).
+pub const MINIMIZE_WEIGHT_ABOVE: FeeRate = FeeRate::from_sat_per_vb_u32(30);
+
+/// Fee rates below this consolidate, see [`AutoPolicy`](crate::policy::AutoPolicy).
+pub const CONSOLIDATE_BELOW: FeeRate = FeeRate::from_sat_per_vb_u32(3);
+
+/// Confirmation targets up to this many blocks are urgent, see
+/// [`ConfTargetPolicy`](crate::policy::ConfTargetPolicy).
+pub const URGENT_WITHIN: u16 = 2;
+
+/// Confirmation targets of at least this many blocks, about a day, are relaxed, see
+/// [`ConfTargetPolicy`](crate::policy::ConfTargetPolicy).
+pub const RELAXED_FROM: u16 = 144;
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{PubkeyHash, ScriptBuf};
+
+    use super::*;
+    use crate::dust::{CoreDust, DustCalculator};
+
+    #[test]
+    fn dust_limit_is_p2pkh_dust() {
+        let p2pkh = ScriptBuf::new_p2pkh(&PubkeyHash::all_zeros());
+        assert_eq!(CoreDust::default().dust_threshold(&p2pkh), DUST_LIMIT);
+    }
+}
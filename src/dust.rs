@@ -0,0 +1,110 @@
+//! Dust thresholds for outputs.
+//!
+//! Whether an output is dust depends on its script: spending a witness output is cheaper
+//! than spending a legacy one, so a witness output may carry a lower value. The
+//! [`DustCalculator`] trait lets callers supply their own policy; [`CoreDust`] mirrors
+//! Bitcoin Core's `GetDustThreshold` and is what
+//! [`CoinSelector::dust`](crate::CoinSelector::dust) decides change versus no change with.
+
+use std::fmt;
+
+use bitcoin::{Amount, FeeRate, Script};
+
+/// Computes the dust threshold of an output.
+pub trait DustCalculator {
+    /// The smallest value an output paying to `script_pubkey` may have without being dust.
+    fn dust_threshold(&self, script_pubkey: &Script) -> Amount;
+
+    /// Returns `true` if an output of `value` paying to `script_pubkey` is dust.
+    fn is_dust(&self, value: Amount, script_pubkey: &Script) -> bool {
+        value < self.dust_threshold(script_pubkey)
+    }
+}
+
+impl fmt::Debug for dyn DustCalculator + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("DustCalculator")
+    }
+}
+
+/// Bitcoin Core's dust policy.
+///
+/// The threshold is the fee, at the dust relay fee rate, for creating the output and
+/// later spending it. `OP_RETURN` outputs are never dust.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoreDust {
+    /// The dust relay fee rate, `-dustrelayfee` in Bitcoin Core.
+    pub dust_relay_fee: FeeRate,
+}
+
+impl CoreDust {
+    /// Bitcoin Core's default dust relay fee rate of 3 sat/vB.
+    pub const DEFAULT_DUST_RELAY_FEE: FeeRate = crate::defaults::DUST_RELAY_FEE;
+}
+
+impl Default for CoreDust {
+    fn default() -> Self {
+        CoreDust {
+            dust_relay_fee: Self::DEFAULT_DUST_RELAY_FEE,
+        }
+    }
+}
+
+impl DustCalculator for CoreDust {
+    fn dust_threshold(&self, script_pubkey: &Script) -> Amount {
+        script_pubkey.minimal_non_dust_custom(self.dust_relay_fee)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{PubkeyHash, ScriptBuf, WPubkeyHash};
+
+    use super::*;
+
+    #[test]
+    fn core_dust_thresholds() {
+        let dust = CoreDust::default();
+
+        let p2pkh = ScriptBuf::new_p2pkh(&PubkeyHash::all_zeros());
+        assert_eq!(dust.dust_threshold(&p2pkh), Amount::from_sat(546));
+
+        let p2wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        assert_eq!(dust.dust_threshold(&p2wpkh), Amount::from_sat(294));
+
+        let op_return = ScriptBuf::new_op_return([0u8; 4]);
+        assert_eq!(dust.dust_threshold(&op_return), Amount::ZERO);
+    }
+
+    #[test]
+    fn is_dust() {
+        let dust = CoreDust::default();
+        let p2wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+
+        assert!(dust.is_dust(Amount::from_sat(293), &p2wpkh));
+        assert!(!dust.is_dust(Amount::from_sat(294), &p2wpkh));
+    }
+
+    #[test]
+    fn custom_dust_relay_fee() {
+        let dust = CoreDust {
+            dust_relay_fee: FeeRate::from_sat_per_vb_u32(1),
+        };
+        let p2pkh = ScriptBuf::new_p2pkh(&PubkeyHash::all_zeros());
+        assert_eq!(dust.dust_threshold(&p2pkh), Amount::from_sat(182));
+    }
+
+    #[test]
+    fn custom_calculator() {
+        struct Flat;
+        impl DustCalculator for Flat {
+            fn dust_threshold(&self, _: &Script) -> Amount {
+                Amount::from_sat(1_000)
+            }
+        }
+
+        let p2wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        assert!(Flat.is_dust(Amount::from_sat(999), &p2wpkh));
+    }
+}
@@ -0,0 +1,114 @@
+//! Fee estimation hooks.
+//!
+//! Instead of passing fee rates directly, selections can be specified by a confirmation
+//! target ("confirm within 3 blocks"), with the rates resolved by a [`FeeEstimator`]
+//! backed by the wallet's fee estimation source, see
+//! [`CoinSelector::estimated_fee_rate`](crate::CoinSelector::estimated_fee_rate).
+//!
+//! The long term fee rate, which values spending an output later, comes from its own
+//! [`LongTermFeeEstimator`] trait, so the waste computation can ask a live source too.
+//! A plain [`FeeRate`] is a long term fee estimator returning itself.
+
+use bitcoin::FeeRate;
+
+/// A source of the long term fee rate.
+pub trait LongTermFeeEstimator {
+    /// The fee rate expected in the long run, used to value spending an output later.
+    fn long_term_fee_rate(&self) -> FeeRate;
+}
+
+impl LongTermFeeEstimator for FeeRate {
+    fn long_term_fee_rate(&self) -> FeeRate {
+        *self
+    }
+}
+
+impl<T: LongTermFeeEstimator + ?Sized> LongTermFeeEstimator for &T {
+    fn long_term_fee_rate(&self) -> FeeRate {
+        (**self).long_term_fee_rate()
+    }
+}
+
+/// A source of fee rate estimates.
+pub trait FeeEstimator: LongTermFeeEstimator {
+    /// The fee rate expected to confirm a transaction within `conf_target` blocks.
+    fn fee_rate(&self, conf_target: u16) -> FeeRate;
+}
+
+impl<T: FeeEstimator + ?Sized> FeeEstimator for &T {
+    fn fee_rate(&self, conf_target: u16) -> FeeRate {
+        (**self).fee_rate(conf_target)
+    }
+}
+
+/// An estimator returning the same rates for every confirmation target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticFeeEstimator {
+    /// Returned by [`FeeEstimator::fee_rate`] for any target.
+    pub fee_rate: FeeRate,
+    /// Returned by [`LongTermFeeEstimator::long_term_fee_rate`].
+    pub long_term_fee_rate: FeeRate,
+}
+
+impl FeeEstimator for StaticFeeEstimator {
+    fn fee_rate(&self, _conf_target: u16) -> FeeRate {
+        self.fee_rate
+    }
+}
+
+impl LongTermFeeEstimator for StaticFeeEstimator {
+    fn long_term_fee_rate(&self) -> FeeRate {
+        self.long_term_fee_rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Halving;
+
+    impl FeeEstimator for Halving {
+        fn fee_rate(&self, conf_target: u16) -> FeeRate {
+            FeeRate::from_sat_per_vb_u32(64 >> conf_target.min(6))
+        }
+    }
+
+    impl LongTermFeeEstimator for Halving {
+        fn long_term_fee_rate(&self) -> FeeRate {
+            FeeRate::from_sat_per_vb_u32(1)
+        }
+    }
+
+    fn resolve<E: FeeEstimator>(estimator: E, conf_target: u16) -> (FeeRate, FeeRate) {
+        (
+            estimator.fee_rate(conf_target),
+            estimator.long_term_fee_rate(),
+        )
+    }
+
+    #[test]
+    fn static_estimator() {
+        let estimator = StaticFeeEstimator {
+            fee_rate: FeeRate::from_sat_per_vb_u32(10),
+            long_term_fee_rate: FeeRate::from_sat_per_vb_u32(5),
+        };
+        assert_eq!(estimator.fee_rate(1), estimator.fee_rate(1008));
+        assert_eq!(
+            estimator.long_term_fee_rate(),
+            FeeRate::from_sat_per_vb_u32(5)
+        );
+    }
+
+    #[test]
+    fn estimator_by_reference() {
+        let estimator: &dyn FeeEstimator = &Halving;
+        assert_eq!(
+            resolve(estimator, 3),
+            (
+                FeeRate::from_sat_per_vb_u32(8),
+                FeeRate::from_sat_per_vb_u32(1)
+            )
+        );
+    }
+}
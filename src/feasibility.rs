@@ -0,0 +1,143 @@
+//! The fee rates at which a payment is possible.
+//!
+//! As fee rates rise, small candidates cost more to spend than they are worth and a pool
+//! that covered a target may no longer do so, or only with more inputs than fit in a
+//! transaction. [`max_feasible_fee_rate`] finds the point where that happens, so a wallet
+//! can tell the user that a payment is only possible below a given fee rate.
+
+use bitcoin::{Amount, FeeRate, Weight};
+
+use crate::candidate::eligible_candidates;
+use crate::coin_grinder::{select_coins_coin_grinder, LimitPolicy};
+use crate::WeightedUtxo;
+
+/// Returns the highest fee rate at which the candidates of `pool` can cover `target` with
+/// inputs weighing at most `max_weight`.
+///
+/// Without the weight limit a selection covering `target` exists exactly when all
+/// candidates with a positive effective value together cover it. When they weigh more than
+/// `max_weight`, [CoinGrinder](select_coins_coin_grinder) searches for the lightest
+/// selection covering `target` instead, so a fee rate is only reported if a selection
+/// within the limit was found. Either way fewer selections cover `target` as the fee rate
+/// rises, so the fee rate is found by binary search.
+///
+/// Returns `None` if `pool` can't cover `target` within `max_weight` even without fees.
+pub fn max_feasible_fee_rate<U: WeightedUtxo>(
+    target: Amount,
+    max_weight: Weight,
+    pool: &[U],
+) -> Option<FeeRate> {
+    let feasible = |sat_per_kwu: u64| {
+        let fee_rate = FeeRate::from_sat_per_kwu(sat_per_kwu);
+        let mut available = Amount::ZERO;
+        let mut weight = Weight::ZERO;
+        for candidate in eligible_candidates(pool, fee_rate, |_| {}) {
+            available = available
+                .checked_add(candidate.effective_value)
+                .unwrap_or(Amount::MAX);
+            weight = weight.checked_add(candidate.weight).unwrap_or(Weight::MAX);
+        }
+        if available < target {
+            return false;
+        }
+        target == Amount::ZERO
+            || weight <= max_weight
+            || select_coins_coin_grinder(
+                target,
+                Amount::ZERO,
+                max_weight,
+                usize::MAX,
+                fee_rate,
+                LimitPolicy::ReturnBest,
+                pool,
+            )
+            .is_ok()
+    };
+
+    if !feasible(0) {
+        return None;
+    }
+
+    // Invariant: `low` is feasible and every rate above `high` is not.
+    let (mut low, mut high) = (0, u64::MAX);
+    while low < high {
+        let mid = low + (high - low).div_ceil(2);
+        if feasible(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Some(FeeRate::from_sat_per_kwu(low))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_pool;
+
+    const NO_LIMIT: Weight = Weight::MAX;
+
+    #[test]
+    fn fee_rate_limit() {
+        // Each 432 wu input pays a fee of 10 000 sats at 23 148 sat/kwu, 10 001 at 23 149.
+        let pool = build_pool(&[(20_000, 272), (20_000, 272)]);
+        let fee_rate = max_feasible_fee_rate(Amount::from_sat(20_000), NO_LIMIT, &pool);
+        assert_eq!(fee_rate, Some(FeeRate::from_sat_per_kwu(23_148)));
+    }
+
+    #[test]
+    fn weight_limit() {
+        // Three inputs cover 15 000 sats while each pays at most 5 000, two only while each
+        // pays at most 2 500.
+        let pool = build_pool(&[(10_000, 272), (10_000, 272), (10_000, 272)]);
+        let target = Amount::from_sat(15_000);
+        assert_eq!(
+            max_feasible_fee_rate(target, NO_LIMIT, &pool),
+            Some(FeeRate::from_sat_per_kwu(11_574))
+        );
+        assert_eq!(
+            max_feasible_fee_rate(target, Weight::from_wu(2 * 432), &pool),
+            Some(FeeRate::from_sat_per_kwu(5_787))
+        );
+        assert_eq!(
+            max_feasible_fee_rate(target, Weight::from_wu(432), &pool),
+            None
+        );
+    }
+
+    #[test]
+    fn single_candidate_limit() {
+        // The 272 wu input pays a fee of 9 999 sats at 36 761 sat/kwu, 10 000 at 36 762.
+        let pool = build_pool(&[(10_000, 112)]);
+        let fee_rate = max_feasible_fee_rate(Amount::from_sat(1), NO_LIMIT, &pool);
+        assert_eq!(fee_rate, Some(FeeRate::from_sat_per_kwu(36_761)));
+    }
+
+    #[test]
+    fn zero_target_is_always_feasible() {
+        let pool = build_pool(&[]);
+        assert_eq!(
+            max_feasible_fee_rate(Amount::ZERO, NO_LIMIT, &pool),
+            Some(FeeRate::from_sat_per_kwu(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn zero_target_needs_no_inputs() {
+        let pool = build_pool(&[(1_000, 272)]);
+        assert_eq!(
+            max_feasible_fee_rate(Amount::ZERO, Weight::ZERO, &pool),
+            Some(FeeRate::from_sat_per_kwu(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn infeasible_without_fees() {
+        let pool = build_pool(&[(1_000, 0)]);
+        assert_eq!(
+            max_feasible_fee_rate(Amount::from_sat(1_001), NO_LIMIT, &pool),
+            None
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Stable fingerprints of selections.
+//!
+//! A fingerprint commits to the parameters of a selection request and to the candidates
+//! that were selected, independent of the order they are listed in. Services can use it
+//! to deduplicate double-submitted payment requests, or to notice that an identical
+//! request now yields a different selection because the pool changed.
+//!
+//! The encoding is part of the API: a fingerprint computed by this version of the crate is
+//! equal to the one computed by any later version. It is the SHA256 of the tag
+//! `coin-selection/fingerprint/v1`, a kind byte (0 for indices, 1 for outpoints), the
+//! target in sats and the fee rate in sat/kwu as little endian `u64`s, the number of
+//! selected candidates as little endian `u64`, and the sorted candidates. Indices are
+//! encoded as little endian `u64` and outpoints by their consensus encoding: the txid
+//! followed by the output index as little endian `u32`.
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::{Amount, FeeRate, OutPoint};
+
+const TAG: &[u8] = b"coin-selection/fingerprint/v1";
+
+/// Fingerprints a selection identified by the indices of the selected candidates.
+///
+/// Indices change whenever the pool is rebuilt, so this is only meaningful for the same
+/// pool. Use [`outpoint_fingerprint`] to compare across pool changes.
+pub fn selection_fingerprint<I>(target: Amount, fee_rate: FeeRate, selected: I) -> sha256::Hash
+where
+    I: IntoIterator<Item = usize>,
+{
+    let mut selected: Vec<usize> = selected.into_iter().collect();
+    selected.sort_unstable();
+
+    let mut engine = engine(0, target, fee_rate, selected.len());
+    for index in selected {
+        engine.input(&(index as u64).to_le_bytes());
+    }
+    sha256::Hash::from_engine(engine)
+}
+
+/// Fingerprints a selection identified by the outpoints of the selected candidates.
+pub fn outpoint_fingerprint<I>(target: Amount, fee_rate: FeeRate, selected: I) -> sha256::Hash
+where
+    I: IntoIterator<Item = OutPoint>,
+{
+    let mut selected: Vec<OutPoint> = selected.into_iter().collect();
+    selected.sort_unstable();
+
+    let mut engine = engine(1, target, fee_rate, selected.len());
+    for outpoint in selected {
+        engine.input(outpoint.txid.as_byte_array());
+        engine.input(&outpoint.vout.to_le_bytes());
+    }
+    sha256::Hash::from_engine(engine)
+}
+
+fn engine(kind: u8, target: Amount, fee_rate: FeeRate, count: usize) -> sha256::HashEngine {
+    let mut engine = sha256::Hash::engine();
+    engine.input(TAG);
+    engine.input(&[kind]);
+    engine.input(&target.to_sat().to_le_bytes());
+    engine.input(&fee_rate.to_sat_per_kwu().to_le_bytes());
+    engine.input(&(count as u64).to_le_bytes());
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Txid;
+
+    use super::*;
+
+    const TARGET: Amount = Amount::from_sat(50_000);
+    const FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_u32(10);
+
+    fn outpoint(byte: u8, vout: u32) -> OutPoint {
+        OutPoint {
+            txid: Txid::from_byte_array([byte; 32]),
+            vout,
+        }
+    }
+
+    #[test]
+    fn fingerprint_ignores_order() {
+        assert_eq!(
+            selection_fingerprint(TARGET, FEE_RATE, vec![3, 1, 2]),
+            selection_fingerprint(TARGET, FEE_RATE, vec![1, 2, 3])
+        );
+        assert_eq!(
+            outpoint_fingerprint(TARGET, FEE_RATE, vec![outpoint(2, 0), outpoint(1, 5)]),
+            outpoint_fingerprint(TARGET, FEE_RATE, vec![outpoint(1, 5), outpoint(2, 0)])
+        );
+    }
+
+    #[test]
+    fn fingerprint_commits_to_params_and_selection() {
+        let fingerprint = selection_fingerprint(TARGET, FEE_RATE, vec![1, 2]);
+
+        assert_ne!(
+            fingerprint,
+            selection_fingerprint(TARGET, FEE_RATE, vec![1, 3])
+        );
+        assert_ne!(
+            fingerprint,
+            selection_fingerprint(TARGET, FEE_RATE, vec![1, 2, 2])
+        );
+        assert_ne!(
+            fingerprint,
+            selection_fingerprint(Amount::from_sat(50_001), FEE_RATE, vec![1, 2])
+        );
+        assert_ne!(
+            fingerprint,
+            selection_fingerprint(TARGET, FeeRate::ZERO, vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn fingerprint_kinds_differ() {
+        assert_ne!(
+            selection_fingerprint(TARGET, FEE_RATE, vec![]),
+            outpoint_fingerprint(TARGET, FEE_RATE, vec![])
+        );
+    }
+
+    #[test]
+    fn fingerprint_is_stable() {
+        assert_eq!(
+            selection_fingerprint(TARGET, FEE_RATE, vec![0, 7]).to_string(),
+            "6621b57af8bba7092fe0bc59ea8bb267261451bd6400c270ff23e464585f62f4"
+        );
+        assert_eq!(
+            outpoint_fingerprint(TARGET, FEE_RATE, vec![outpoint(1, 1)]).to_string(),
+            "0cbad1f91660e5782faedfb2c0240d086ca3fa416011d2fb5ab7124963a00460"
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! Grouping candidates by script.
+//!
+//! Spending one coin sent to an address reveals that the wallet owns the others, so once
+//! one of them is spent they may as well all be. Like Bitcoin Core, candidates paying to
+//! the same script can be grouped with [`group_by_script`] and selected as a unit. Groups
+//! implement [`WeightedUtxo`], so every selector accepts a pool of groups.
+
+use std::collections::BTreeMap;
+
+use bitcoin::{Amount, FeeRate, Script, SignedAmount, Weight};
+
+use crate::{UtxoMetadata, WeightedUtxo};
+
+/// The most candidates in a group, as in Bitcoin Core. Further candidates paying to the
+/// same script start a new group.
+pub const MAX_GROUP_SIZE: usize = 100;
+
+/// Candidates paying to the same script, selected together.
+#[derive(Debug)]
+pub struct OutputGroup<'a, U> {
+    utxos: Vec<(usize, &'a U)>,
+    value: Amount,
+    satisfaction_weight: Weight,
+    weight_margin: Weight,
+    fee_adjustment: SignedAmount,
+    ancestor_fee: Amount,
+    ancestor_weight: Weight,
+}
+
+impl<U> Clone for OutputGroup<'_, U> {
+    fn clone(&self) -> Self {
+        OutputGroup {
+            utxos: self.utxos.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<'a, U> OutputGroup<'a, U> {
+    /// The candidates of the group with their indices in the pool.
+    pub fn utxos(&self) -> &[(usize, &'a U)] {
+        &self.utxos
+    }
+}
+
+impl<U: WeightedUtxo> WeightedUtxo for OutputGroup<'_, U> {
+    /// The weight of spending every candidate of the group without their margins, less
+    /// the base weight of one input, so that [`WeightedUtxo::weight`] is the weight of all
+    /// of the inputs.
+    fn satisfaction_weight(&self) -> Weight {
+        self.satisfaction_weight
+    }
+
+    fn value(&self) -> Amount {
+        self.value
+    }
+
+    /// The margins of all candidates of the group.
+    fn weight_margin(&self) -> Weight {
+        self.weight_margin
+    }
+
+    /// The fee adjustments of all candidates of the group.
+    fn fee_adjustment(&self) -> SignedAmount {
+        self.fee_adjustment
+    }
+
+    /// The ancestor fees of all candidates of the group.
+    fn ancestor_fee(&self) -> Amount {
+        self.ancestor_fee
+    }
+
+    /// The ancestor weights of all candidates of the group.
+    fn ancestor_weight(&self) -> Weight {
+        self.ancestor_weight
+    }
+
+    /// The bump fees of all candidates of the group, so that ancestors paying more than
+    /// needed for one candidate don't pay for the ancestors of another.
+    fn bump_fee(&self, fee_rate: FeeRate) -> Option<Amount> {
+        self.utxos.iter().try_fold(Amount::ZERO, |sum, (_, utxo)| {
+            sum.checked_add(utxo.bump_fee(fee_rate)?)
+        })
+    }
+
+    /// A group is frozen if any of its candidates is.
+    fn is_spendable(&self) -> bool {
+        self.utxos.iter().all(|(_, utxo)| utxo.is_spendable())
+    }
+}
+
+/// Groups the candidates of `pool` by script.
+///
+/// Groups are ordered by their first candidate in `pool` and hold at most
+/// [`MAX_GROUP_SIZE`] candidates. Fees for a group are computed on its total weight, so a
+/// group's effective value can exceed the sum of its candidates' by less than a satoshi
+/// per candidate.
+///
+/// Returns `None` if the value or weight of a group overflows.
+pub fn group_by_script<U>(pool: &[U]) -> Option<Vec<OutputGroup<'_, U>>>
+where
+    U: WeightedUtxo + UtxoMetadata,
+{
+    let mut groups: Vec<OutputGroup<'_, U>> = Vec::new();
+    let mut open: BTreeMap<&Script, usize> = BTreeMap::new();
+
+    for (index, utxo) in pool.iter().enumerate() {
+        let script = utxo.script_pubkey();
+        match open.get(script) {
+            Some(&group) if groups[group].utxos.len() < MAX_GROUP_SIZE => {
+                let group = &mut groups[group];
+                group.value = group.value.checked_add(utxo.value())?;
+                let margin = utxo.weight_margin();
+                group.satisfaction_weight = group
+                    .satisfaction_weight
+                    .checked_add(utxo.weight()?.checked_sub(margin)?)?;
+                group.weight_margin = group.weight_margin.checked_add(margin)?;
+                group.fee_adjustment = group.fee_adjustment.checked_add(utxo.fee_adjustment())?;
+                group.ancestor_fee = group.ancestor_fee.checked_add(utxo.ancestor_fee())?;
+                group.ancestor_weight =
+                    group.ancestor_weight.checked_add(utxo.ancestor_weight())?;
+                group.utxos.push((index, utxo));
+            }
+            _ => {
+                open.insert(script, groups.len());
+                groups.push(OutputGroup {
+                    utxos: vec![(index, utxo)],
+                    value: utxo.value(),
+                    satisfaction_weight: utxo.satisfaction_weight(),
+                    weight_margin: utxo.weight_margin(),
+                    fee_adjustment: utxo.fee_adjustment(),
+                    ancestor_fee: utxo.ancestor_fee(),
+                    ancestor_weight: utxo.ancestor_weight(),
+                });
+            }
+        }
+    }
+    Some(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{FeeRate, OutPoint, ScriptBuf, WPubkeyHash};
+
+    use super::*;
+    use crate::prefix_sum::select_coins_prefix_sum;
+
+    struct Coin {
+        value: Amount,
+        script_pubkey: ScriptBuf,
+        fee_adjustment: SignedAmount,
+        ancestor_fee: Amount,
+    }
+
+    impl WeightedUtxo for Coin {
+        fn satisfaction_weight(&self) -> Weight {
+            Weight::from_wu(108)
+        }
+
+        fn value(&self) -> Amount {
+            self.value
+        }
+
+        fn fee_adjustment(&self) -> SignedAmount {
+            self.fee_adjustment
+        }
+
+        fn ancestor_fee(&self) -> Amount {
+            self.ancestor_fee
+        }
+
+        fn ancestor_weight(&self) -> Weight {
+            Weight::from_wu(400)
+        }
+    }
+
+    impl UtxoMetadata for Coin {
+        fn outpoint(&self) -> OutPoint {
+            OutPoint::null()
+        }
+
+        fn script_pubkey(&self) -> &Script {
+            &self.script_pubkey
+        }
+    }
+
+    fn pool(coins: &[(u64, u8)]) -> Vec<Coin> {
+        coins
+            .iter()
+            .map(|&(value, key)| Coin {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([key; 20])),
+                fee_adjustment: SignedAmount::ZERO,
+                ancestor_fee: Amount::ZERO,
+            })
+            .collect()
+    }
+
+    fn indices<U>(group: &OutputGroup<'_, U>) -> Vec<usize> {
+        group.utxos().iter().map(|&(index, _)| index).collect()
+    }
+
+    #[test]
+    fn groups_aggregate_candidates() {
+        let pool = pool(&[(1_000, 1), (2_000, 2), (3_000, 1)]);
+        let groups = group_by_script(&pool).unwrap();
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(indices(&groups[0]), vec![0, 2]);
+        assert_eq!(indices(&groups[1]), vec![1]);
+        assert_eq!(groups[0].value(), Amount::from_sat(4_000));
+        assert_eq!(groups[0].weight(), Some(Weight::from_wu(2 * 268)));
+        assert_eq!(groups[1].weight(), Some(Weight::from_wu(268)));
+    }
+
+    #[test]
+    fn groups_sum_fee_adjustments() {
+        let mut pool = pool(&[(10_000, 1), (10_000, 2), (10_000, 1)]);
+        pool[0].fee_adjustment = SignedAmount::from_sat(500);
+        pool[2].fee_adjustment = SignedAmount::from_sat(-200);
+        let groups = group_by_script(&pool).unwrap();
+
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        assert_eq!(groups[0].fee_adjustment(), SignedAmount::from_sat(300));
+        let members =
+            pool[0].effective_value(fee_rate).unwrap() + pool[2].effective_value(fee_rate).unwrap();
+        assert_eq!(groups[0].effective_value(fee_rate), Some(members));
+    }
+
+    #[test]
+    fn group_bump_fee_is_per_candidate() {
+        let mut pool = pool(&[(10_000, 1), (10_000, 1)]);
+        pool[0].ancestor_fee = Amount::from_sat(3_000);
+        let groups = group_by_script(&pool).unwrap();
+
+        // Each 100 vB parent needs 1 000 sats at 10 sat/vB, and the first overpaying
+        // doesn't help the second.
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        assert_eq!(groups[0].ancestor_fee(), Amount::from_sat(3_000));
+        assert_eq!(groups[0].ancestor_weight(), Weight::from_wu(800));
+        assert_eq!(groups[0].bump_fee(fee_rate), Some(Amount::from_sat(1_000)));
+    }
+
+    #[test]
+    fn groups_are_capped() {
+        let pool = pool(&[(1_000, 1); MAX_GROUP_SIZE + 1]);
+        let groups = group_by_script(&pool).unwrap();
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].utxos().len(), MAX_GROUP_SIZE);
+        assert_eq!(indices(&groups[1]), vec![MAX_GROUP_SIZE]);
+    }
+
+    #[test]
+    fn groups_are_selected_whole() {
+        let pool = pool(&[(5_000, 1), (7_000, 2), (1_000, 1)]);
+        let groups = group_by_script(&pool).unwrap();
+
+        let report =
+            select_coins_prefix_sum(Amount::from_sat(5_500), FeeRate::ZERO, &groups).unwrap();
+        let selected: Vec<usize> = report
+            .selection
+            .iter()
+            .flat_map(|c| indices(c.utxo))
+            .collect();
+        assert_eq!(selected, vec![0, 2]);
+    }
+}
@@ -0,0 +1,370 @@
+//! Knapsack approximation.
+//!
+//! This is the selector Bitcoin Core falls back to when no changeless solution is found.
+//! It looks for a candidate matching the target exactly, then for a subset of the
+//! candidates smaller than the target plus a minimum change, using a randomized
+//! approximation of the subset sum closest to the target. The result is compared with the
+//! smallest candidate larger than that and the better of the two is returned.
+
+use bitcoin::{Amount, FeeRate};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::candidate::{eligible_candidates, Candidate};
+use crate::order::sort_candidates;
+use crate::target::check_target;
+use crate::{SelectionError, SelectionReport, WeightedUtxo};
+
+/// Number of random subsets tried when approximating the best subset, as in Bitcoin Core.
+pub const ITERATIONS: usize = 1_000;
+
+/// Bitcoin Core's minimum change of 0.01 BTC.
+pub const DEFAULT_MIN_CHANGE: Amount = Amount::from_sat(1_000_000);
+
+/// Selects candidates covering `target` the way Bitcoin Core's knapsack solver does.
+///
+/// Effective values at `fee_rate` are used throughout. A subset exceeding `target` is only
+/// accepted if the excess is at least `min_change`, so that the change output is worth
+/// creating; otherwise the smallest single candidate covering `target` is preferred.
+///
+/// If `max_input_value` is given, the values of the selected candidates sum to at most
+/// that. Subsets exceeding it are not considered.
+///
+/// The selection is in canonical order and the report counts the subsets tried.
+pub fn select_coins_knapsack<'a, U, R>(
+    target: Amount,
+    min_change: Amount,
+    max_input_value: Option<Amount>,
+    fee_rate: FeeRate,
+    pool: &'a [U],
+    rng: &mut R,
+) -> Result<SelectionReport<'a, U>, SelectionError>
+where
+    U: WeightedUtxo,
+    R: Rng + ?Sized,
+{
+    check_target(target)?;
+    let cap_exceeded = |max_input_value| SelectionError::MaxInputValueExceeded {
+        target,
+        max_input_value,
+    };
+    let required = target;
+    let target = target.to_sat();
+    let target_with_change = target.saturating_add(min_change.to_sat());
+    let cap = max_input_value.map_or(u64::MAX, Amount::to_sat);
+
+    let mut candidates = eligible_candidates(pool, fee_rate, |_| {});
+    candidates.retain(|c| c.utxo.value().to_sat() <= cap);
+    candidates.shuffle(rng);
+
+    let mut lowest_larger: Option<Candidate<'a, U>> = None;
+    let mut applicable = Vec::new();
+    let mut total_lower: u64 = 0;
+    let mut total_lower_input: u64 = 0;
+    for candidate in candidates {
+        let value = candidate.effective_value.to_sat();
+        if value == target {
+            return SelectionReport::new(vec![candidate], required, 0);
+        } else if value < target_with_change {
+            total_lower = total_lower.saturating_add(value);
+            total_lower_input = total_lower_input.saturating_add(candidate.utxo.value().to_sat());
+            applicable.push(candidate);
+        } else if lowest_larger.map_or(true, |l| candidate.effective_value < l.effective_value) {
+            lowest_larger = Some(candidate);
+        }
+    }
+
+    if total_lower == target && total_lower_input <= cap {
+        sort_candidates(&mut applicable);
+        return SelectionReport::new(applicable, required, 0);
+    }
+    if total_lower < target {
+        return match lowest_larger {
+            Some(candidate) => SelectionReport::new(vec![candidate], required, 0),
+            None => Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(total_lower),
+                required,
+            }),
+        };
+    }
+
+    sort_candidates(&mut applicable);
+    let values: Vec<(u64, u64)> = applicable
+        .iter()
+        .map(|c| (c.effective_value.to_sat(), c.utxo.value().to_sat()))
+        .collect();
+    let all = (total_lower_input <= cap).then_some(total_lower);
+
+    let mut best = approximate_best_subset(rng, &values, all, target, cap);
+    if best.sum != Some(target) && total_lower >= target_with_change {
+        let iterations = best.iterations;
+        best = approximate_best_subset(rng, &values, all, target_with_change, cap);
+        best.iterations += iterations;
+    }
+    let iterations = best.iterations;
+
+    if let Some(lowest_larger) = lowest_larger {
+        let lowest_larger_value = lowest_larger.effective_value.to_sat();
+        let use_lowest_larger = match best.sum {
+            Some(sum) => (sum != target && sum < target_with_change) || lowest_larger_value <= sum,
+            None => true,
+        };
+        if use_lowest_larger {
+            return SelectionReport::new(vec![lowest_larger], required, iterations);
+        }
+    }
+    if best.sum.is_none() {
+        return Err(cap_exceeded(Amount::from_sat(cap)));
+    }
+
+    let selection = applicable
+        .into_iter()
+        .zip(best.included)
+        .filter(|(_, included)| *included)
+        .map(|(candidate, _)| candidate)
+        .collect();
+    SelectionReport::new(selection, required, iterations)
+}
+
+/// The best subset found by [`approximate_best_subset`].
+struct Subset {
+    /// Which values are included.
+    included: Vec<bool>,
+    /// Sum of the included effective values, `None` if no subset within the cap was found.
+    sum: Option<u64>,
+    /// Number of iterations run.
+    iterations: usize,
+}
+
+/// Approximates the subset of `values` with the smallest effective value sum of at least
+/// `target` whose value sum doesn't exceed `cap`.
+///
+/// `values` holds the effective value and value of each candidate, sorted by descending
+/// effective value. `all` is the effective value sum of all of them, or `None` if their
+/// value sum exceeds `cap`, and must be at least `target`. Each iteration includes every
+/// candidate with probability one half and, if the target isn't reached, completes the
+/// subset with the remaining candidates in order. Candidates that would exceed `cap` are
+/// skipped. Whenever the target is reached, the last candidate is removed again to try to
+/// get closer.
+fn approximate_best_subset<R: Rng + ?Sized>(
+    rng: &mut R,
+    values: &[(u64, u64)],
+    all: Option<u64>,
+    target: u64,
+    cap: u64,
+) -> Subset {
+    let mut best = Subset {
+        included: vec![all.is_some(); values.len()],
+        sum: all,
+        iterations: 0,
+    };
+
+    while best.iterations < ITERATIONS && best.sum != Some(target) {
+        best.iterations += 1;
+        let mut included = vec![false; values.len()];
+        let mut sum: u64 = 0;
+        let mut input_sum: u64 = 0;
+        let mut reached_target = false;
+        for pass in 0..2 {
+            if reached_target {
+                break;
+            }
+            for (i, &(value, input_value)) in values.iter().enumerate() {
+                let include = if pass == 0 { rng.gen() } else { !included[i] };
+                let within_cap = input_sum
+                    .checked_add(input_value)
+                    .is_some_and(|input_sum| input_sum <= cap);
+                if include && within_cap {
+                    sum = sum.saturating_add(value);
+                    input_sum += input_value;
+                    included[i] = true;
+                    if sum >= target {
+                        reached_target = true;
+                        if best.sum.map_or(true, |best| sum < best) {
+                            best.sum = Some(sum);
+                            best.included.clone_from(&included);
+                        }
+                        sum -= value;
+                        input_sum -= input_value;
+                        included[i] = false;
+                    }
+                }
+            }
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::build_pool;
+
+    const MIN_CHANGE: Amount = Amount::from_sat(1_000);
+
+    fn select(target: u64, values: &[u64]) -> Result<Vec<u64>, SelectionError> {
+        let pool: Vec<_> = build_pool(&values.iter().map(|&v| (v, 0)).collect::<Vec<_>>());
+        let mut rng = StdRng::seed_from_u64(42);
+        select_coins_knapsack(
+            Amount::from_sat(target),
+            MIN_CHANGE,
+            None,
+            FeeRate::ZERO,
+            &pool,
+            &mut rng,
+        )
+        .map(|report| {
+            report
+                .selection
+                .iter()
+                .map(|c| c.effective_value.to_sat())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn exact_match() {
+        assert_eq!(select(5_000, &[9_000, 5_000, 3_000]), Ok(vec![5_000]));
+    }
+
+    #[test]
+    fn all_smaller_candidates_match() {
+        assert_eq!(
+            select(6_000, &[4_000, 20_000, 2_000]),
+            Ok(vec![4_000, 2_000])
+        );
+    }
+
+    #[test]
+    fn smaller_candidates_insufficient() {
+        assert_eq!(
+            select(7_000, &[4_000, 20_000, 2_000, 9_000]),
+            Ok(vec![9_000])
+        );
+    }
+
+    #[test]
+    fn exact_subset_is_found() {
+        assert_eq!(
+            select(10_000, &[6_000, 2_500, 1_500, 3_000, 4_000, 700]),
+            Ok(vec![6_000, 2_500, 1_500])
+        );
+    }
+
+    #[test]
+    fn subset_leaves_min_change() {
+        // No subset hits 5 000 exactly, so the closest leaving 1 000 of change is used.
+        assert_eq!(
+            select(5_000, &[4_200, 1_900, 1_800, 300]),
+            Ok(vec![4_200, 1_800])
+        );
+    }
+
+    #[test]
+    fn lowest_larger_beats_small_change() {
+        // 4 200 + 900 leaves too little change, the lowest larger candidate is preferred.
+        assert_eq!(select(5_000, &[4_200, 900, 6_500]), Ok(vec![6_500]));
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        assert_eq!(
+            select(10_000, &[4_000, 2_000]),
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(6_000),
+                required: Amount::from_sat(10_000),
+            })
+        );
+    }
+
+    #[test]
+    fn iterations_are_reported() {
+        let pool = build_pool(&[(4_200, 0), (1_900, 0), (1_800, 0), (300, 0), (9_000, 0)]);
+        let mut rng = StdRng::seed_from_u64(42);
+        let target = Amount::from_sat(5_000);
+
+        let report =
+            select_coins_knapsack(target, MIN_CHANGE, None, FeeRate::ZERO, &pool, &mut rng)
+                .unwrap();
+        // No subset hits 5 000 exactly, so the first approximation runs to the end before
+        // the second one looks for 6 000.
+        assert!(report.iterations > ITERATIONS);
+        assert_eq!(report.excess, Amount::from_sat(1_000));
+
+        let report = select_coins_knapsack(
+            target,
+            MIN_CHANGE,
+            None,
+            FeeRate::ZERO,
+            &pool[..1],
+            &mut rng,
+        );
+        assert_eq!(
+            report.map(|r| r.iterations),
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(4_200),
+                required: target,
+            })
+        );
+    }
+
+    #[test]
+    fn max_input_value() {
+        let pool = build_pool(&[(6_000, 0), (2_500, 0), (1_500, 0), (3_000, 0), (4_000, 0)]);
+        let select = |target: u64, max: u64| {
+            let mut rng = StdRng::seed_from_u64(42);
+            select_coins_knapsack(
+                Amount::from_sat(target),
+                MIN_CHANGE,
+                Some(Amount::from_sat(max)),
+                FeeRate::ZERO,
+                &pool,
+                &mut rng,
+            )
+            .map(|report| report.input_value.to_sat())
+        };
+
+        // Exact matches within the cap.
+        assert_eq!(select(10_000, 20_000), Ok(10_000));
+        assert_eq!(select(7_000, 8_000), Ok(7_000));
+        assert_eq!(select(9_000, 9_500), Ok(9_000));
+        // No subset sums to between 5 600 and 5 800.
+        assert_eq!(
+            select(5_600, 5_800),
+            Err(SelectionError::MaxInputValueExceeded {
+                target: Amount::from_sat(5_600),
+                max_input_value: Amount::from_sat(5_800),
+            })
+        );
+        assert_eq!(
+            select(10_001, 10_000),
+            Err(SelectionError::MaxInputValueExceeded {
+                target: Amount::from_sat(10_001),
+                max_input_value: Amount::from_sat(10_000),
+            })
+        );
+    }
+
+    #[test]
+    fn huge_values() {
+        let huge = i64::MAX as u64;
+        assert_eq!(
+            select(huge - 1, &[huge / 2, huge / 2, huge / 2, huge]),
+            Ok(vec![huge / 2, huge / 2])
+        );
+    }
+
+    #[test]
+    fn zero_target() {
+        assert_eq!(
+            select(0, &[4_000]),
+            Err(SelectionError::InvalidTarget(
+                crate::target::InvalidTargetError::Zero
+            ))
+        );
+    }
+}
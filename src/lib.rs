@@ -1,7 +1,2654 @@
+//! Rust Bitcoin coin selection library.
+//!
+//! Candidates are described by the [`WeightedUtxo`] trait: the value of the output and the
+//! weight needed to satisfy its script. From those the fee for spending a candidate, and
+//! with it the candidate's effective value, can be derived at any fee rate.
+//!
+//! Features that need more than that, such as privacy scoring, ask for it through the
+//! optional [`UtxoMetadata`] and [`UtxoChainInfo`] traits, so candidates only implement
+//! what the features they use require.
+//!
+//! # Panics
+//!
+//! No function of this crate panics, whatever its inputs, unless its documentation has a
+//! `# Panics` section. Embedders that must not panic, such as signing devices, can rely
+//! on this for every other function. Within the crate, `unwrap`, `expect` and `panic!`
+//! are denied outside of tests.
+
+#![cfg_attr(
+    not(test),
+    deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)
+)]
+
+use std::fmt;
+
+use bitcoin::{Amount, FeeRate, OutPoint, Script, SignedAmount, TxOut, Weight};
+
+use crate::dust::DustCalculator;
+
+pub mod best;
+pub mod candidate;
+pub mod capabilities;
+pub mod change;
+pub mod coin_grinder;
+pub mod consolidate;
+pub mod defaults;
+pub mod dust;
+pub mod estimator;
+pub mod feasibility;
+pub mod fingerprint;
+pub mod group;
+pub mod knapsack;
+#[cfg(feature = "linkability")]
+pub mod linkability;
+pub mod lint;
+pub mod maturity;
+pub mod objective;
+pub mod order;
+pub mod pair;
+pub mod partition;
+pub mod policy;
+pub mod prefix_sum;
+pub mod preview;
+pub mod privacy;
+pub mod psbt;
+pub mod repair;
+pub mod sample;
+pub mod srd;
+pub mod standardness;
+pub mod target;
+pub mod utxo;
+pub mod waste;
+pub mod weight;
+
+pub use crate::weight::predict_transaction_weight;
+
+/// Behavior needed for coin selection.
+pub trait WeightedUtxo {
+    /// The weight of the `scriptSig` and witness needed to spend this output.
+    ///
+    /// See the [`weight`] module for presets of common script types.
+    ///
+    /// A satisfaction weight of zero is valid and means the input only pays for its
+    /// outpoint and sequence. Such candidates are very cheap to spend and will be
+    /// preferred by every algorithm. Since a zero weight is more often a forgotten value
+    /// than a real script, [`lint::ZeroWeightPolicy`] can be used to flag or reject them.
+    fn satisfaction_weight(&self) -> Weight;
+
+    /// The value of the output.
+    fn value(&self) -> Amount;
+
+    /// An amount added to the effective value of this output, zero by default.
+    ///
+    /// Accounting systems may credit a coin with part of a fee already paid, for example
+    /// when the wallet overpaid for the transaction creating it, or charge it extra. A
+    /// positive adjustment makes the coin more attractive to every algorithm, a negative
+    /// one less. The fee actually paid for the input is not affected.
+    fn fee_adjustment(&self) -> SignedAmount {
+        SignedAmount::ZERO
+    }
+
+    /// Whether this output may be selected, `true` by default.
+    ///
+    /// Returning `false` freezes the output, for example because it is dust or its
+    /// address was disclosed, without removing it from the pool. Frozen outputs are
+    /// rejected with [`RejectReason::Frozen`](candidate::RejectReason::Frozen) when
+    /// candidates are prepared, so no algorithm selects them.
+    fn is_spendable(&self) -> bool {
+        true
+    }
+
+    /// Extra weight reserved for spending this output, zero by default.
+    ///
+    /// Signatures are occasionally larger than estimated and some signers add witness
+    /// items, so a margin can be reserved on top of the satisfaction weight. It counts
+    /// wherever the weight does: in the effective value, the weight of a selection and
+    /// the maximum weight. [`weight::Padded`] adds the same margin to every candidate.
+    fn weight_margin(&self) -> Weight {
+        Weight::ZERO
+    }
+
+    /// The fees paid by the unconfirmed ancestors of this output, zero by default.
+    ///
+    /// See [`bump_fee`](Self::bump_fee).
+    fn ancestor_fee(&self) -> Amount {
+        Amount::ZERO
+    }
+
+    /// The weight of the unconfirmed ancestors of this output, zero by default.
+    ///
+    /// See [`bump_fee`](Self::bump_fee).
+    fn ancestor_weight(&self) -> Weight {
+        Weight::ZERO
+    }
+
+    /// The fee needed to bring the unconfirmed ancestors of this output up to `fee_rate`.
+    ///
+    /// Miners only include a transaction together with its ancestors, so spending
+    /// unconfirmed change created at a lower fee rate has to pay for the shortfall too. The
+    /// bump fee is the fee for the [ancestor weight](Self::ancestor_weight) at `fee_rate`
+    /// less the [ancestor fee](Self::ancestor_fee), and zero for outputs whose ancestors
+    /// already pay enough. It is deducted from the effective value.
+    ///
+    /// Bump fees are computed per output, so an ancestor shared by several selected
+    /// outputs, such as a transaction creating two of them, is paid for by each and the
+    /// fee of the selection is overestimated.
+    ///
+    /// Returns `None` on overflow.
+    fn bump_fee(&self, fee_rate: FeeRate) -> Option<Amount> {
+        let required = fee_rate.fee_wu(self.ancestor_weight())?;
+        Some(
+            required
+                .checked_sub(self.ancestor_fee())
+                .unwrap_or(Amount::ZERO),
+        )
+    }
+
+    /// The total weight this output adds to a transaction spending it, including the
+    /// [margin](Self::weight_margin).
+    ///
+    /// Returns `None` on overflow.
+    fn weight(&self) -> Option<Weight> {
+        defaults::TX_IN_BASE_WEIGHT
+            .checked_add(self.satisfaction_weight())?
+            .checked_add(self.weight_margin())
+    }
+
+    /// The value of the output minus the fee needed to spend it at `fee_rate` and the
+    /// [bump fee](Self::bump_fee), plus the [fee adjustment](Self::fee_adjustment).
+    ///
+    /// Returns `None` on overflow.
+    fn effective_value(&self, fee_rate: FeeRate) -> Option<SignedAmount> {
+        effective_value(fee_rate, self.weight()?, self.value())?
+            .checked_sub(self.bump_fee(fee_rate)?.to_signed().ok()?)?
+            .checked_add(self.fee_adjustment())
+    }
+}
+
+impl<T: WeightedUtxo + ?Sized> WeightedUtxo for &T {
+    fn satisfaction_weight(&self) -> Weight {
+        (**self).satisfaction_weight()
+    }
+
+    fn value(&self) -> Amount {
+        (**self).value()
+    }
+
+    fn weight_margin(&self) -> Weight {
+        (**self).weight_margin()
+    }
+
+    fn fee_adjustment(&self) -> SignedAmount {
+        (**self).fee_adjustment()
+    }
+
+    fn is_spendable(&self) -> bool {
+        (**self).is_spendable()
+    }
+
+    fn ancestor_fee(&self) -> Amount {
+        (**self).ancestor_fee()
+    }
+
+    fn ancestor_weight(&self) -> Weight {
+        (**self).ancestor_weight()
+    }
+}
+
+/// Identity of a candidate on chain.
+pub trait UtxoMetadata {
+    /// The outpoint of the output.
+    fn outpoint(&self) -> OutPoint;
+
+    /// The script locking the output.
+    fn script_pubkey(&self) -> &Script;
+}
+
+impl<T: UtxoMetadata + ?Sized> UtxoMetadata for &T {
+    fn outpoint(&self) -> OutPoint {
+        (**self).outpoint()
+    }
+
+    fn script_pubkey(&self) -> &Script {
+        (**self).script_pubkey()
+    }
+}
+
+/// Chain state of a candidate.
+pub trait UtxoChainInfo {
+    /// Number of confirmations of the output, zero if it is unconfirmed.
+    fn confirmations(&self) -> u32;
+
+    /// Returns `true` if the output is confirmed.
+    fn is_confirmed(&self) -> bool {
+        self.confirmations() > 0
+    }
+
+    /// Returns `true` if the output was created by a coinbase transaction, `false` by
+    /// default.
+    fn is_coinbase(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if a transaction spending the output can be mined in the next
+    /// block, which for coinbase outputs takes
+    /// [`COINBASE_MATURITY`](maturity::COINBASE_MATURITY) confirmations.
+    fn is_mature(&self) -> bool {
+        !self.is_coinbase() || self.confirmations() >= maturity::COINBASE_MATURITY
+    }
+}
+
+impl<T: UtxoChainInfo + ?Sized> UtxoChainInfo for &T {
+    fn confirmations(&self) -> u32 {
+        (**self).confirmations()
+    }
+
+    fn is_coinbase(&self) -> bool {
+        (**self).is_coinbase()
+    }
+}
+
+/// Computes `value` minus the fee for `weight` at `fee_rate`.
+///
+/// Returns `None` on overflow.
+pub fn effective_value(fee_rate: FeeRate, weight: Weight, value: Amount) -> Option<SignedAmount> {
+    let fee = fee_rate.fee_wu(weight)?.to_signed().ok()?;
+    value.to_signed().ok()?.checked_sub(fee)
+}
+
+/// The fee paid for spending `candidate`, its value minus its effective value without the
+/// fee adjustment.
+fn candidate_fee<U: WeightedUtxo>(candidate: &candidate::Candidate<'_, U>) -> Option<Amount> {
+    candidate
+        .utxo
+        .value()
+        .to_signed()
+        .ok()?
+        .checked_sub(candidate.effective_value.to_signed().ok()?)?
+        .checked_add(candidate.utxo.fee_adjustment())?
+        .to_unsigned()
+        .ok()
+}
+
+/// Reason a selector found no selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SelectionError {
+    /// The eligible candidates can't cover the target.
+    InsufficientFunds {
+        /// Sum of the effective values of the eligible candidates.
+        available: Amount,
+        /// The target.
+        required: Amount,
+    },
+    /// The target can't be selected for, for example because it is zero. Every selector
+    /// checks its target before looking at the pool.
+    InvalidTarget(target::InvalidTargetError),
+    /// Summing the values of the selection overflowed.
+    Overflow,
+    /// The search gave up before finding a selection.
+    IterationLimitReached,
+    /// Every selection covering the target weighs more than the maximum.
+    MaxWeightExceeded {
+        /// The maximum weight of the selected inputs.
+        max_weight: Weight,
+    },
+    /// Every selection covering the target has more inputs than the maximum.
+    MaxInputCountExceeded {
+        /// The maximum number of selected inputs.
+        max_input_count: usize,
+    },
+    /// No selection covering the target stays within the maximum input value.
+    MaxInputValueExceeded {
+        /// The target.
+        target: Amount,
+        /// The maximum total value of the selected candidates.
+        max_input_value: Amount,
+    },
+    /// A preset input is not in the pool, is frozen or costs at least its value to spend.
+    InvalidPresetInput(usize),
+    /// The fee of the selection exceeds the most a payment should pay, which usually means
+    /// the fee rate is in the wrong unit.
+    FeeExceedsPayment {
+        /// The fee paid for the selected inputs.
+        fee: Amount,
+        /// The most the fee may be.
+        max_fee: Amount,
+    },
+    /// The spend of the change script can't be inferred, see
+    /// [`ChangeOutput::from_script`](change::ChangeOutput::from_script).
+    UnsupportedChangeScript,
+    /// The selection would not produce a standard transaction, see
+    /// [`CoinSelector::standardness`].
+    NonStandard(standardness::StandardnessError),
+}
+
+impl fmt::Display for SelectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectionError::InsufficientFunds {
+                available,
+                required,
+            } => write!(
+                f,
+                "insufficient funds: {} available, {} required",
+                available, required
+            ),
+            SelectionError::InvalidTarget(e) => write!(f, "invalid target: {}", e),
+            SelectionError::Overflow => write!(f, "selection value overflows"),
+            SelectionError::IterationLimitReached => write!(f, "iteration limit reached"),
+            SelectionError::MaxWeightExceeded { max_weight } => write!(
+                f,
+                "every selection weighs more than the maximum of {}",
+                max_weight
+            ),
+            SelectionError::MaxInputCountExceeded { max_input_count } => write!(
+                f,
+                "every selection has more inputs than the maximum of {}",
+                max_input_count
+            ),
+            SelectionError::MaxInputValueExceeded {
+                target,
+                max_input_value,
+            } => write!(
+                f,
+                "no selection covering {} stays within the maximum input value of {}",
+                target, max_input_value
+            ),
+            SelectionError::InvalidPresetInput(index) => {
+                write!(f, "preset input {} can't be selected", index)
+            }
+            SelectionError::FeeExceedsPayment { fee, max_fee } => write!(
+                f,
+                "fee of {} exceeds the maximum of {} for the payment",
+                fee, max_fee
+            ),
+            SelectionError::UnsupportedChangeScript => {
+                write!(
+                    f,
+                    "change script type requires an explicit satisfaction weight"
+                )
+            }
+            SelectionError::NonStandard(e) => write!(f, "non-standard selection: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SelectionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SelectionError::InvalidTarget(e) => Some(e),
+            SelectionError::NonStandard(e) => Some(e),
+            SelectionError::InsufficientFunds { .. }
+            | SelectionError::Overflow
+            | SelectionError::IterationLimitReached
+            | SelectionError::MaxWeightExceeded { .. }
+            | SelectionError::MaxInputCountExceeded { .. }
+            | SelectionError::MaxInputValueExceeded { .. }
+            | SelectionError::InvalidPresetInput(_)
+            | SelectionError::FeeExceedsPayment { .. }
+            | SelectionError::UnsupportedChangeScript => None,
+        }
+    }
+}
+
+impl From<target::InvalidTargetError> for SelectionError {
+    fn from(e: target::InvalidTargetError) -> Self {
+        SelectionError::InvalidTarget(e)
+    }
+}
+
+/// What a selection pays for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Purpose {
+    /// A payment to someone else.
+    Payment,
+    /// A transfer between accounts of the same wallet, where all outputs come back.
+    SelfTransfer,
+}
+
+/// A selection with the numbers describing it.
+///
+/// With the `serde` feature a report can be serialized for audits, but not deserialized,
+/// since it borrows the selected candidates from the pool.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SelectionReport<'a, U> {
+    /// The selected candidates, in canonical order.
+    pub selection: Vec<candidate::Candidate<'a, U>>,
+    /// Sum of the values of the selected candidates.
+    pub input_value: Amount,
+    /// Sum of the effective values of the selected candidates.
+    pub effective_value: Amount,
+    /// Sum of the weights of the selected inputs.
+    pub weight: Weight,
+    /// Part of the weight reserved as [margin](WeightedUtxo::weight_margin). The fee for
+    /// it is part of the fee.
+    pub weight_margin: Weight,
+    /// The fee paid for the selected inputs at the selection fee rate, including the
+    /// [bump fees](WeightedUtxo::bump_fee) of their ancestors and not counting fee
+    /// adjustments.
+    pub fee: Amount,
+    /// The effective value exceeding the target.
+    pub excess: Amount,
+    /// Number of search iterations, zero for selectors that don't search.
+    pub iterations: usize,
+    /// What the selection pays for, so accounting can tell transfers from payments.
+    /// Selectors report [`Purpose::Payment`].
+    pub purpose: Purpose,
+    /// Whether only a sample of the pool was searched, see [`sample`].
+    pub sampled: bool,
+    /// Whether the search stopped at its iteration limit, so a better selection may exist.
+    pub limit_hit: bool,
+    /// The change the selection was asked to leave over the target, for selectors given a
+    /// change target.
+    pub change_target: Option<Amount>,
+    /// The value of the change output, for selections given a
+    /// [change script](CoinSelector::change_script),
+    /// [change output](CoinSelector::change_output) or
+    /// [denominations](CoinSelector::change_denominations), or `None` if the excess goes
+    /// to fees.
+    pub change: Option<Amount>,
+    /// The candidates of the pool dropped before selecting and why, for selections made
+    /// by [`CoinSelector`].
+    pub rejections: Vec<candidate::Rejection>,
+    /// The [privacy score](privacy::privacy_score) of the selection, for selections made by
+    /// [`CoinSelector`] from candidates with [`UtxoMetadata`].
+    pub privacy: Option<privacy::PrivacyScore>,
+}
+
+impl<U> Clone for SelectionReport<'_, U> {
+    fn clone(&self) -> Self {
+        SelectionReport {
+            selection: self.selection.clone(),
+            rejections: self.rejections.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<'a, U: WeightedUtxo> SelectionReport<'a, U> {
+    /// Describes `selection`, which covers `target` and took `iterations` to find.
+    ///
+    /// Returns [`SelectionError::Overflow`] if a sum overflows and
+    /// [`SelectionError::InsufficientFunds`] if `selection` does not cover `target`.
+    pub fn new(
+        selection: Vec<candidate::Candidate<'a, U>>,
+        target: Amount,
+        iterations: usize,
+    ) -> Result<Self, SelectionError> {
+        let mut input_value = Amount::ZERO;
+        let mut effective_value = Amount::ZERO;
+        let mut weight = Weight::ZERO;
+        let mut weight_margin = Weight::ZERO;
+        let mut fee = Amount::ZERO;
+        for candidate in &selection {
+            input_value = input_value
+                .checked_add(candidate.utxo.value())
+                .ok_or(SelectionError::Overflow)?;
+            effective_value = effective_value
+                .checked_add(candidate.effective_value)
+                .ok_or(SelectionError::Overflow)?;
+            weight = weight
+                .checked_add(candidate.weight)
+                .ok_or(SelectionError::Overflow)?;
+            weight_margin = weight_margin
+                .checked_add(candidate.utxo.weight_margin())
+                .ok_or(SelectionError::Overflow)?;
+            fee = candidate_fee(candidate)
+                .and_then(|candidate_fee| fee.checked_add(candidate_fee))
+                .ok_or(SelectionError::Overflow)?;
+        }
+        let excess =
+            effective_value
+                .checked_sub(target)
+                .ok_or(SelectionError::InsufficientFunds {
+                    available: effective_value,
+                    required: target,
+                })?;
+
+        Ok(SelectionReport {
+            selection,
+            input_value,
+            effective_value,
+            weight,
+            weight_margin,
+            fee,
+            excess,
+            iterations,
+            purpose: Purpose::Payment,
+            sampled: false,
+            limit_hit: false,
+            change_target: None,
+            change: None,
+            rejections: Vec::new(),
+            privacy: None,
+        })
+    }
+
+    /// The waste of the selection, as defined by Bitcoin Core.
+    ///
+    /// This is the fee paid for the inputs now minus what spending them at the long term
+    /// fee rate would cost, plus `change_cost`, the cost of creating and later spending the
+    /// change output. Without change, `change_cost` is `None` and the excess, which goes
+    /// to fees, is added instead.
+    ///
+    /// The long term fee rate is asked from `long_term`, once per call. It can be a
+    /// [`FeeRate`] or a wallet's estimator.
+    ///
+    /// Returns `None` on overflow.
+    pub fn waste<E: estimator::LongTermFeeEstimator>(
+        &self,
+        long_term: E,
+        change_cost: Option<Amount>,
+    ) -> Option<SignedAmount> {
+        let long_term_fee_rate = long_term.long_term_fee_rate();
+        let mut long_term_fee = Amount::ZERO;
+        for candidate in &self.selection {
+            long_term_fee =
+                long_term_fee.checked_add(long_term_fee_rate.fee_wu(candidate.weight)?)?;
+        }
+        let timing = self
+            .fee
+            .to_signed()
+            .ok()?
+            .checked_sub(long_term_fee.to_signed().ok()?)?;
+        timing.checked_add(change_cost.unwrap_or(self.excess).to_signed().ok()?)
+    }
+
+    /// The selected candidates with their pool indices, sorted into `order`.
+    pub fn inputs(&self, order: order::InputOrder) -> Vec<(usize, &'a U)> {
+        let mut selection = self.selection.clone();
+        order::sort_inputs(&mut selection, order);
+        selection.iter().map(|c| (c.index, c.utxo)).collect()
+    }
+}
+
+impl<'a, U: UtxoMetadata> SelectionReport<'a, U> {
+    /// The selected candidates with their pool indices, sorted as BIP 69 sorts inputs.
+    pub fn inputs_bip69(&self) -> Vec<(usize, &'a U)> {
+        let mut selection = self.selection.clone();
+        order::sort_bip69(&mut selection);
+        selection.iter().map(|c| (c.index, c.utxo)).collect()
+    }
+}
+
+/// Selects coins from a pool, configured one parameter at a time.
+///
+/// Parameters that are not set keep their defaults, so new ones can be added without
+/// breaking callers.
+///
+/// ```
+/// use bitcoin::{Amount, FeeRate};
+/// use rust_bitcoin_coin_selection::{CoinSelector, WeightedUtxo};
+/// # use bitcoin::Weight;
+/// # struct Utxo(Amount);
+/// # impl WeightedUtxo for Utxo {
+/// #     fn satisfaction_weight(&self) -> Weight { Weight::from_wu(272) }
+/// #     fn value(&self) -> Amount { self.0 }
+/// # }
+/// # let pool = vec![Utxo(Amount::from_sat(100_000)), Utxo(Amount::from_sat(30_000))];
+///
+/// let report = CoinSelector::new(&pool)
+///     .target(Amount::from_sat(120_000))
+///     .fee_rate(FeeRate::from_sat_per_vb_u32(5))
+///     .min_change(Amount::from_sat(5_000))
+///     .select()
+///     .expect("pool covers the target");
+/// assert_eq!(report.selection.len(), 2);
+/// ```
+#[derive(Debug)]
+pub struct CoinSelector<'a, U> {
+    pool: &'a [U],
+    target: Amount,
+    target_kind: Option<target::TargetKind>,
+    fee_rate: FeeRate,
+    min_change: Amount,
+    max_input_value: Option<Amount>,
+    max_weight: Weight,
+    max_input_count: usize,
+    max_change: Option<Amount>,
+    policy: policy::AutoPolicy,
+    strategy: Option<policy::Strategy>,
+    purpose: Purpose,
+    preset_inputs: &'a [usize],
+    on_limit: coin_grinder::LimitPolicy,
+    random_change_fee: Option<Amount>,
+    max_fee_multiple: Option<u64>,
+    min_effective_value: Amount,
+    subtract_fee_from_outputs: bool,
+    change_script: Option<&'a Script>,
+    change_output: Option<change::ChangeOutput>,
+    discard_fee_rate: FeeRate,
+    dust: &'a dyn DustCalculator,
+    change_denominations: Option<&'a change::DenominationLadder>,
+    standardness: Option<(standardness::StandardnessLimits, &'a [TxOut])>,
+}
+
+impl<U> Clone for CoinSelector<'_, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<U> Copy for CoinSelector<'_, U> {}
+
+impl<'a, U: WeightedUtxo> CoinSelector<'a, U> {
+    /// Starts configuring a selection from `pool`.
+    ///
+    /// The target and fee rate default to zero, the minimum change to
+    /// [`knapsack::DEFAULT_MIN_CHANGE`], the input weight to
+    /// [`MAX_STANDARD_TX_WEIGHT`](defaults::MAX_STANDARD_TX_WEIGHT) and the policy to
+    /// [`AutoPolicy::default`](policy::AutoPolicy::default). The input value is not
+    /// limited, neither are the number of inputs and the change, the selection is for a
+    /// [`Purpose::Payment`], no inputs are preset, the inputs fund the fee, which may not
+    /// exceed the target, the best selection found within the iteration limit is returned,
+    /// the change output is unknown and spent at
+    /// [`DISCARD_FEE_RATE`](change::DISCARD_FEE_RATE), dust is [Bitcoin Core's](dust::CoreDust::default),
+    /// change is not rounded and selections are not checked for standardness.
+    pub fn new(pool: &'a [U]) -> Self {
+        CoinSelector {
+            pool,
+            target: Amount::ZERO,
+            target_kind: None,
+            fee_rate: FeeRate::ZERO,
+            min_change: knapsack::DEFAULT_MIN_CHANGE,
+            max_input_value: None,
+            max_weight: defaults::MAX_STANDARD_TX_WEIGHT,
+            max_input_count: usize::MAX,
+            max_change: None,
+            policy: policy::AutoPolicy::default(),
+            strategy: None,
+            purpose: Purpose::Payment,
+            preset_inputs: &[],
+            on_limit: coin_grinder::LimitPolicy::ReturnBest,
+            random_change_fee: None,
+            max_fee_multiple: Some(1),
+            min_effective_value: Amount::ZERO,
+            subtract_fee_from_outputs: false,
+            change_script: None,
+            change_output: None,
+            discard_fee_rate: change::DISCARD_FEE_RATE,
+            dust: &dust::CoreDust {
+                dust_relay_fee: dust::CoreDust::DEFAULT_DUST_RELAY_FEE,
+            },
+            change_denominations: None,
+            standardness: None,
+        }
+    }
+
+    /// Sets the amount the effective values of the selection must cover.
+    pub fn target(mut self, target: Amount) -> Self {
+        self.target = target;
+        self.target_kind = None;
+        self
+    }
+
+    /// Sets the target from what it includes, resolved at the fee rate of the selection.
+    ///
+    /// A [`TargetKind::RecipientsPlusOverhead`](target::TargetKind::RecipientsPlusOverhead)
+    /// target adds the fee for the rest of the transaction at the fee rate set when
+    /// selecting, or none if the fee is
+    /// [subtracted from the outputs](Self::subtract_fee_from_outputs). A signed target is
+    /// classified with `TargetKind::try_from`, which fails if it is zero or negative.
+    pub fn target_kind(mut self, target_kind: target::TargetKind) -> Self {
+        self.target_kind = Some(target_kind);
+        self
+    }
+
+    /// Sets the fee rate the selection is made at.
+    pub fn fee_rate(mut self, fee_rate: FeeRate) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    /// Sets the fee rate to what `estimator` expects to confirm within `conf_target`
+    /// blocks.
+    pub fn estimated_fee_rate<E: estimator::FeeEstimator>(
+        mut self,
+        conf_target: u16,
+        estimator: E,
+    ) -> Self {
+        self.fee_rate = estimator.fee_rate(conf_target);
+        self
+    }
+
+    /// Sets the smallest change worth creating when the target can't be matched exactly.
+    pub fn min_change(mut self, min_change: Amount) -> Self {
+        self.min_change = min_change;
+        self
+    }
+
+    /// Limits the total value of the selected candidates, for example to bound the funds
+    /// moved by a single transaction.
+    pub fn max_input_value(mut self, max_input_value: Amount) -> Self {
+        self.max_input_value = Some(max_input_value);
+        self
+    }
+
+    /// Limits the total weight of the selected inputs when minimizing weight.
+    pub fn max_weight(mut self, max_weight: Weight) -> Self {
+        self.max_weight = max_weight;
+        self
+    }
+
+    /// Limits the number of selected inputs, for example for signing devices that can't
+    /// handle large transactions.
+    pub fn max_input_count(mut self, max_input_count: usize) -> Self {
+        self.max_input_count = max_input_count;
+        self
+    }
+
+    /// Sets the most change a selection should leave, for example
+    /// [`CHANGE_UPPER`](srd::CHANGE_UPPER).
+    ///
+    /// Large change reveals much of the wallet's balance, so a selection leaving more is
+    /// only returned if no alternative leaves less.
+    pub fn max_change(mut self, max_change: Amount) -> Self {
+        self.max_change = Some(max_change);
+        self
+    }
+
+    /// Sets the policy choosing the algorithm from the fee rate, replacing the strategy of
+    /// a [confirmation target](Self::conf_target).
+    pub fn policy(mut self, policy: policy::AutoPolicy) -> Self {
+        self.policy = policy;
+        self.strategy = None;
+        self
+    }
+
+    /// Selects for a payment that should confirm within `conf_target` blocks.
+    ///
+    /// The fee rate is estimated by `estimator` and `policy` chooses the algorithm from the
+    /// urgency of the payment, see
+    /// [`ConfTargetPolicy::preset`](policy::ConfTargetPolicy::preset). Setting the fee rate
+    /// afterwards keeps the algorithm.
+    pub fn conf_target<E: estimator::FeeEstimator>(
+        mut self,
+        conf_target: u16,
+        policy: policy::ConfTargetPolicy,
+        estimator: E,
+    ) -> Self {
+        let preset = policy.preset(conf_target, estimator);
+        self.fee_rate = preset.fee_rate;
+        self.strategy = Some(preset.strategy);
+        self
+    }
+
+    /// Sets what the selection pays for, see [`select_with_rng`](Self::select_with_rng).
+    pub fn purpose(mut self, purpose: Purpose) -> Self {
+        self.purpose = purpose;
+        self
+    }
+
+    /// Sets the pool indices of inputs that must be spent, as chosen with coin control.
+    ///
+    /// They count towards the target, the maximum input value, weight and input count, and
+    /// only what they leave of the target is selected from the rest of the pool. Duplicate
+    /// indices are spent once.
+    pub fn preset_inputs(mut self, preset_inputs: &'a [usize]) -> Self {
+        self.preset_inputs = preset_inputs;
+        self
+    }
+
+    /// Sets what the weight minimizing search does when it reaches its iteration limit.
+    pub fn on_iteration_limit(mut self, on_limit: coin_grinder::LimitPolicy) -> Self {
+        self.on_limit = on_limit;
+        self
+    }
+
+    /// Draws the minimum change for each selection, like Bitcoin Core, so change amounts
+    /// don't give the wallet away.
+    ///
+    /// The minimum change is drawn with [`srd::generate_change_target`] for the target and
+    /// `change_fee`, the fee for the change output, replacing the configured one.
+    pub fn randomize_change_target(mut self, change_fee: Amount) -> Self {
+        self.random_change_fee = Some(change_fee);
+        self
+    }
+
+    /// Limits the fee of a payment to `max_fee_multiple` times the target, or lifts the
+    /// limit if `None`.
+    ///
+    /// A fee rate given in the wrong unit, sat/kvB instead of sat/vB for example, easily
+    /// makes a selection pay more in fees than it pays out, so by default the fee may not
+    /// exceed the target.
+    pub fn max_fee_multiple(mut self, max_fee_multiple: Option<u64>) -> Self {
+        self.max_fee_multiple = max_fee_multiple;
+        self
+    }
+
+    /// Ignores candidates whose effective value is below `min_effective_value`, which
+    /// shrinks the search space of dust-heavy pools.
+    ///
+    /// Preset inputs are spent regardless. The ignored candidates are reported in the
+    /// [rejections](SelectionReport::rejections) of the selection.
+    pub fn min_effective_value(mut self, min_effective_value: Amount) -> Self {
+        self.min_effective_value = min_effective_value;
+        self
+    }
+
+    /// Subtracts the fee from the recipient outputs instead of funding it with more inputs,
+    /// like Bitcoin Core's `subtractfeefromamount`.
+    ///
+    /// The target is then the sum of the recipient amounts, without any fee, and is
+    /// covered by the values of the selected candidates. The fee for the inputs, reported
+    /// as the fee of the selection, and the fee for the rest of the transaction are to be
+    /// deducted from the recipient outputs. The policy still chooses the algorithm from the
+    /// fee rate.
+    pub fn subtract_fee_from_outputs(mut self, subtract_fee_from_outputs: bool) -> Self {
+        self.subtract_fee_from_outputs = subtract_fee_from_outputs;
+        self
+    }
+
+    /// Sets the script of the change output.
+    ///
+    /// Unless a [change output](Self::change_output) is given, the fee and cost of change
+    /// are derived from the script, see [`change::ChangeOutput::from_script`]. The script
+    /// also decides whether the change is [dust](Self::dust).
+    pub fn change_script(mut self, change_script: &'a Script) -> Self {
+        self.change_script = Some(change_script);
+        self
+    }
+
+    /// Describes the change output by the weights of creating and spending it, for change
+    /// scripts whose spend can't be inferred.
+    ///
+    /// The selection then has to leave the minimum change plus the fee for the change
+    /// output, the report gives the [change](SelectionReport::change) left after that fee,
+    /// and [`waste`](Self::waste) charges the cost of change to selections leaving some.
+    pub fn change_output(mut self, change_output: change::ChangeOutput) -> Self {
+        self.change_output = Some(change_output);
+        self
+    }
+
+    /// Sets the fee rate the change output is expected to be spent at, which
+    /// [`waste`](Self::waste) charges selections leaving change for. Defaults to
+    /// [`DISCARD_FEE_RATE`](change::DISCARD_FEE_RATE).
+    pub fn discard_fee_rate(mut self, discard_fee_rate: FeeRate) -> Self {
+        self.discard_fee_rate = discard_fee_rate;
+        self
+    }
+
+    /// Sets the dust policy deciding whether the change left by a selection is worth an
+    /// output.
+    ///
+    /// With a [change script](Self::change_script), the minimum change is raised to the
+    /// dust threshold of the script, and change below it goes to fees instead. Any
+    /// [`DustCalculator`] can be used, [`CoreDust`](dust::CoreDust) is the default.
+    pub fn dust(mut self, dust: &'a dyn DustCalculator) -> Self {
+        self.dust = dust;
+        self
+    }
+
+    /// Rounds the change down to one of `denominations`, paying the rest as fee, so it is
+    /// more likely to be spent without change later.
+    ///
+    /// The minimum change is raised to the smallest denomination not below it, so the
+    /// change rounds to at least that, and [`waste`](Self::waste) counts the rounding as
+    /// fee.
+    pub fn change_denominations(mut self, denominations: &'a change::DenominationLadder) -> Self {
+        self.change_denominations = Some(denominations);
+        self
+    }
+
+    /// Checks every selection against `limits` before returning it.
+    ///
+    /// `outputs` are the outputs of the transaction other than change. The
+    /// [change](SelectionReport::change) is added to them when there is a
+    /// [change script](Self::change_script), and the outputs are checked against the
+    /// [dust policy](Self::dust). Selections that would not be standard are returned as
+    /// [`SelectionError::NonStandard`].
+    pub fn standardness(
+        mut self,
+        limits: standardness::StandardnessLimits,
+        outputs: &'a [TxOut],
+    ) -> Self {
+        self.standardness = Some((limits, outputs));
+        self
+    }
+
+    /// Selects coins, drawing randomness from `rng`.
+    ///
+    /// When the policy chooses [`Strategy::MinimizeWeight`](policy::Strategy::MinimizeWeight)
+    /// the lightest selection leaving the minimum change is searched for with
+    /// [`coin_grinder::select_coins_coin_grinder`]. Otherwise, or if that finds no
+    /// selection within the maximum input value, input count and change,
+    /// [`knapsack::select_coins_knapsack`] is used, unless it leaves too much change too.
+    /// If the search reaches its iteration limit and the
+    /// [limit policy](Self::on_iteration_limit) asks for an error,
+    /// [`SelectionError::IterationLimitReached`] is returned instead. Since knapsack doesn't
+    /// limit the number of inputs, [`SelectionError::MaxInputCountExceeded`] is returned if
+    /// its selection has too many.
+    ///
+    /// When the policy chooses [`Strategy::Consolidate`](policy::Strategy::Consolidate), a
+    /// payment spends the smallest candidates leaving the minimum change first, with
+    /// [`consolidate::select_coins_consolidate`], and falls back to knapsack the same way.
+    ///
+    /// A [`Purpose::SelfTransfer`] needs no minimum change, since the change stays in the
+    /// wallet like the rest of the transfer. Unless the policy minimizes weight, it spends
+    /// the smallest candidates first with [`consolidate::select_coins_consolidate`]. The
+    /// report carries the purpose and the minimum change as its change target.
+    ///
+    /// If the preset inputs cover the target, they are selected alone. Returns
+    /// [`SelectionError::InvalidPresetInput`] if one of them is out of bounds or has no
+    /// positive effective value.
+    ///
+    /// Returns [`SelectionError::FeeExceedsPayment`] if the fee for the inputs of a
+    /// [`Purpose::Payment`] exceeds the [limit](Self::max_fee_multiple), and
+    /// [`SelectionError::InvalidTarget`] if the target was left at zero.
+    /// [`SelectionError::UnsupportedChangeScript`] is returned if the spend of the
+    /// [change script](Self::change_script) can't be inferred, and
+    /// [`SelectionError::NonStandard`] if the selection fails the
+    /// [standardness checks](Self::standardness).
+    ///
+    /// If the fee is [subtracted from the outputs](Self::subtract_fee_from_outputs), the
+    /// values of the candidates cover the target and the report's effective values are
+    /// their values, while its fee is the fee for the inputs at the fee rate plus their
+    /// [bump fees](WeightedUtxo::bump_fee).
+    pub fn select_with_rng<R: rand::Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<SelectionReport<'a, U>, SelectionError> {
+        if self.target_kind.is_some() {
+            return self.resolve_target()?.select_with_rng(rng);
+        }
+        target::check_target(self.target)?;
+        let report = if self.min_effective_value > Amount::ZERO {
+            self.select_above_floor(rng)?
+        } else {
+            self.select_from_pool(rng)?
+        };
+        let mut report = self.check_fee(report)?;
+        report.change = self.change(&report)?;
+        self.check_standardness(&report)?;
+        report.rejections = self.rejections();
+        Ok(report)
+    }
+
+    /// The candidates [`select_with_rng`](Self::select_with_rng) drops, except preset
+    /// inputs below the minimum effective value, which are spent regardless.
+    fn rejections(&self) -> Vec<candidate::Rejection> {
+        let mut rejections = candidate::rejected_candidates_with_floor(
+            self.pool,
+            self.selection_fee_rate(),
+            self.min_effective_value,
+        );
+        rejections.retain(|rejection| {
+            !matches!(rejection.reason, candidate::RejectReason::BelowFloor { .. })
+                || !self.preset_inputs.contains(&rejection.index)
+        });
+        rejections
+    }
+
+    /// Replaces the [target kind](Self::target_kind) by the target it resolves to at the
+    /// selection fee rate.
+    fn resolve_target(&self) -> Result<Self, SelectionError> {
+        match self.target_kind {
+            Some(target_kind) => {
+                let target = target_kind
+                    .effective_target(self.selection_fee_rate())
+                    .ok_or(SelectionError::Overflow)?;
+                Ok(self.target(target))
+            }
+            None => Ok(*self),
+        }
+    }
+
+    /// Selects coins for [`select_with_rng`](Self::select_with_rng) from the whole pool.
+    fn select_from_pool<R: rand::Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<SelectionReport<'a, U>, SelectionError> {
+        let min_change = match self.random_change_fee {
+            Some(change_fee) => srd::generate_change_target(self.target, change_fee, rng)
+                .ok_or(SelectionError::Overflow)?,
+            None => self.min_change,
+        };
+        let selector = CoinSelector {
+            min_change: self.change_target(min_change)?,
+            random_change_fee: None,
+            ..*self
+        };
+        let mut report = if selector.preset_inputs.is_empty() {
+            selector.select_for_purpose(rng)?
+        } else {
+            selector.select_with_preset_inputs(rng)?
+        };
+        report.purpose = self.purpose;
+        Ok(report)
+    }
+
+    /// Runs the [standardness checks](Self::standardness), if any, on `report`.
+    fn check_standardness(&self, report: &SelectionReport<'a, U>) -> Result<(), SelectionError> {
+        let Some((limits, outputs)) = self.standardness else {
+            return Ok(());
+        };
+        let mut outputs = outputs.to_vec();
+        if let (Some(value), Some(script)) = (report.change, self.change_script) {
+            outputs.push(TxOut {
+                value,
+                script_pubkey: script.to_owned(),
+            });
+        }
+        standardness::check_standardness(
+            report.selection.iter().map(|c| c.utxo),
+            &outputs,
+            &limits,
+            self.dust,
+        )
+        .map_err(SelectionError::NonStandard)?;
+        Ok(())
+    }
+
+    /// The [change output](Self::change_output), or the one described by the
+    /// [change script](Self::change_script), if any.
+    fn resolve_change_output(&self) -> Result<Option<change::ChangeOutput>, SelectionError> {
+        match (self.change_output, self.change_script) {
+            (Some(change_output), _) => Ok(Some(change_output)),
+            (None, Some(script)) => change::ChangeOutput::from_script(script)
+                .map(Some)
+                .map_err(|_| SelectionError::UnsupportedChangeScript),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// The fee for the change output at the selection fee rate, zero without a change
+    /// output.
+    fn change_fee(&self) -> Result<Amount, SelectionError> {
+        match self.resolve_change_output()? {
+            Some(change) => change
+                .change_fee(self.selection_fee_rate())
+                .ok_or(SelectionError::Overflow),
+            None => Ok(Amount::ZERO),
+        }
+    }
+
+    /// The excess a selection must leave for a change output of `min_change`, or more if
+    /// that would be dust or isn't a denomination.
+    fn change_target(&self, min_change: Amount) -> Result<Amount, SelectionError> {
+        let mut min_change = match self.change_script {
+            Some(script) => min_change.max(self.dust.dust_threshold(script)),
+            None => min_change,
+        };
+        if let Some(ladder) = self.change_denominations {
+            if let Some(&rung) = ladder.denominations().iter().find(|&&d| d >= min_change) {
+                min_change = rung;
+            }
+        }
+        min_change
+            .checked_add(self.change_fee()?)
+            .ok_or(SelectionError::Overflow)
+    }
+
+    /// The change output `report` leaves once it paid for itself and was rounded down to a
+    /// denomination, unless it is dust. `None` without a change output or denominations.
+    fn change(&self, report: &SelectionReport<'a, U>) -> Result<Option<Amount>, SelectionError> {
+        if self.change_script.is_none()
+            && self.change_output.is_none()
+            && self.change_denominations.is_none()
+        {
+            return Ok(None);
+        }
+        let mut change = report
+            .excess
+            .checked_sub(self.change_fee()?)
+            .filter(|&change| change > Amount::ZERO);
+        if let Some(ladder) = self.change_denominations {
+            change = change.and_then(|change| ladder.round_down(change));
+        }
+        if let Some(script) = self.change_script {
+            change = change.filter(|&change| !self.dust.is_dust(change, script));
+        }
+        Ok(change)
+    }
+
+    /// The [waste](SelectionReport::waste) of `report`, a selection made by this selector,
+    /// with the long term fee rate asked from `long_term`.
+    ///
+    /// A selection leaving [change](SelectionReport::change) is charged the cost of
+    /// creating the change output at the fee rate and spending it later at the
+    /// [discard fee rate](Self::discard_fee_rate), like Bitcoin Core, plus what
+    /// rounding the change to a [denomination](Self::change_denominations) paid as fee.
+    /// Otherwise its excess goes to fees.
+    ///
+    /// Returns `None` on overflow or if the spend of the change script can't be inferred.
+    pub fn waste<E: estimator::LongTermFeeEstimator>(
+        &self,
+        report: &SelectionReport<'a, U>,
+        long_term: E,
+    ) -> Option<SignedAmount> {
+        let change_cost = match report.change {
+            Some(change) => {
+                let cost_of_change = match self.resolve_change_output().ok()? {
+                    Some(output) => output.cost_of_change(self.fee_rate, self.discard_fee_rate)?,
+                    None => Amount::ZERO,
+                };
+                let rounding = report
+                    .excess
+                    .checked_sub(self.change_fee().ok()?)?
+                    .checked_sub(change)?;
+                Some(cost_of_change.checked_add(rounding)?)
+            }
+            None => None,
+        };
+        report.waste(long_term, change_cost)
+    }
+
+    /// Charges the fee of the selected inputs when it is subtracted from the outputs, and
+    /// checks it against the [limit](Self::max_fee_multiple).
+    fn check_fee(
+        &self,
+        mut report: SelectionReport<'a, U>,
+    ) -> Result<SelectionReport<'a, U>, SelectionError> {
+        if self.subtract_fee_from_outputs {
+            let mut fee = self
+                .fee_rate
+                .fee_wu(report.weight)
+                .ok_or(SelectionError::Overflow)?;
+            for candidate in &report.selection {
+                fee = candidate
+                    .utxo
+                    .bump_fee(self.fee_rate)
+                    .and_then(|bump_fee| fee.checked_add(bump_fee))
+                    .ok_or(SelectionError::Overflow)?;
+            }
+            report.fee = fee;
+        }
+        if let (Purpose::Payment, Some(multiple)) = (self.purpose, self.max_fee_multiple) {
+            let max_fee = self.target.checked_mul(multiple).unwrap_or(Amount::MAX);
+            if report.fee > max_fee {
+                return Err(SelectionError::FeeExceedsPayment {
+                    fee: report.fee,
+                    max_fee,
+                });
+            }
+        }
+        Ok(report)
+    }
+
+    /// The fee rate effective values are computed at, zero if the fee is subtracted from
+    /// the outputs.
+    fn selection_fee_rate(&self) -> FeeRate {
+        if self.subtract_fee_from_outputs {
+            FeeRate::ZERO
+        } else {
+            self.fee_rate
+        }
+    }
+
+    /// Selects from the candidates of at least the minimum effective value and the preset
+    /// inputs.
+    fn select_above_floor<R: rand::Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<SelectionReport<'a, U>, SelectionError> {
+        let mut kept: Vec<usize> = candidate::eligible_candidates_with_floor(
+            self.pool,
+            self.selection_fee_rate(),
+            self.min_effective_value,
+            |_| {},
+        )
+        .iter()
+        .map(|c| c.index)
+        .collect();
+        for &index in self.preset_inputs {
+            if index >= self.pool.len() {
+                return Err(SelectionError::InvalidPresetInput(index));
+            }
+            if !kept.contains(&index) {
+                kept.push(index);
+            }
+        }
+        let kept_pool: Vec<&'a U> = kept.iter().map(|&index| &self.pool[index]).collect();
+        let preset_inputs: Vec<usize> = self
+            .preset_inputs
+            .iter()
+            .filter_map(|index| kept.iter().position(|k| k == index))
+            .collect();
+
+        let selector = CoinSelector {
+            pool: &kept_pool,
+            target: self.target,
+            target_kind: None,
+            fee_rate: self.fee_rate,
+            min_change: self.min_change,
+            max_input_value: self.max_input_value,
+            max_weight: self.max_weight,
+            max_input_count: self.max_input_count,
+            max_change: self.max_change,
+            policy: self.policy,
+            strategy: self.strategy,
+            purpose: self.purpose,
+            preset_inputs: &preset_inputs,
+            on_limit: self.on_limit,
+            random_change_fee: self.random_change_fee,
+            max_fee_multiple: self.max_fee_multiple,
+            min_effective_value: Amount::ZERO,
+            subtract_fee_from_outputs: self.subtract_fee_from_outputs,
+            change_script: self.change_script,
+            change_output: self.change_output,
+            discard_fee_rate: self.discard_fee_rate,
+            dust: self.dust,
+            change_denominations: self.change_denominations,
+            standardness: self.standardness,
+        };
+        let report = selector.select_from_pool(rng).map_err(|e| match e {
+            SelectionError::InvalidPresetInput(index) => {
+                SelectionError::InvalidPresetInput(kept[index])
+            }
+            e => e,
+        })?;
+
+        let mut selection: Vec<candidate::Candidate<'a, U>> = report
+            .selection
+            .iter()
+            .map(|c| candidate::Candidate {
+                index: kept[c.index],
+                utxo: *c.utxo,
+                effective_value: c.effective_value,
+                weight: c.weight,
+            })
+            .collect();
+        order::sort_candidates(&mut selection);
+        Ok(SelectionReport {
+            sampled: report.sampled,
+            limit_hit: report.limit_hit,
+            change_target: report.change_target,
+            purpose: report.purpose,
+            ..SelectionReport::new(selection, self.target, report.iterations)?
+        })
+    }
+
+    /// Selects the preset inputs and what they leave of the target from the rest of the
+    /// pool.
+    fn select_with_preset_inputs<R: rand::Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<SelectionReport<'a, U>, SelectionError> {
+        let mut is_preset = vec![false; self.pool.len()];
+        for &index in self.preset_inputs {
+            *is_preset
+                .get_mut(index)
+                .ok_or(SelectionError::InvalidPresetInput(index))? = true;
+        }
+        let mut selection: Vec<candidate::Candidate<'a, U>> =
+            candidate::eligible_candidates(self.pool, self.selection_fee_rate(), |_| {})
+                .into_iter()
+                .filter(|c| is_preset[c.index])
+                .collect();
+        if let Some(&index) = self
+            .preset_inputs
+            .iter()
+            .find(|&&index| !selection.iter().any(|c| c.index == index))
+        {
+            return Err(SelectionError::InvalidPresetInput(index));
+        }
+
+        let preset = SelectionReport::new(selection.clone(), Amount::ZERO, 0)?;
+        if preset.selection.len() > self.max_input_count {
+            return Err(SelectionError::MaxInputCountExceeded {
+                max_input_count: self.max_input_count,
+            });
+        }
+        if preset.effective_value >= self.target {
+            order::sort_candidates(&mut selection);
+            return SelectionReport::new(selection, self.target, 0);
+        }
+        let max_input_value = match self.max_input_value {
+            Some(max) => Some(max.checked_sub(preset.input_value).ok_or(
+                SelectionError::MaxInputValueExceeded {
+                    target: self.target,
+                    max_input_value: max,
+                },
+            )?),
+            None => None,
+        };
+
+        let rest: Vec<(usize, &'a U)> = self
+            .pool
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| !is_preset[index])
+            .collect();
+        let rest_pool: Vec<&'a U> = rest.iter().map(|&(_, utxo)| utxo).collect();
+        let selector = CoinSelector {
+            pool: &rest_pool,
+            target: self.target - preset.effective_value,
+            target_kind: None,
+            fee_rate: self.fee_rate,
+            min_change: self.min_change,
+            max_input_value,
+            max_weight: self
+                .max_weight
+                .checked_sub(preset.weight)
+                .unwrap_or(Weight::ZERO),
+            max_input_count: self.max_input_count - preset.selection.len(),
+            max_change: self.max_change,
+            policy: self.policy,
+            strategy: self.strategy,
+            purpose: self.purpose,
+            preset_inputs: &[],
+            on_limit: self.on_limit,
+            random_change_fee: None,
+            max_fee_multiple: None,
+            min_effective_value: Amount::ZERO,
+            subtract_fee_from_outputs: self.subtract_fee_from_outputs,
+            change_script: self.change_script,
+            change_output: self.change_output,
+            discard_fee_rate: self.discard_fee_rate,
+            dust: self.dust,
+            change_denominations: self.change_denominations,
+            standardness: self.standardness,
+        };
+        let report = selector.select_for_purpose(rng).map_err(|e| match e {
+            SelectionError::InsufficientFunds { available, .. } => {
+                SelectionError::InsufficientFunds {
+                    available: available
+                        .checked_add(preset.effective_value)
+                        .unwrap_or(Amount::MAX),
+                    required: self.target,
+                }
+            }
+            SelectionError::MaxInputValueExceeded { .. } => SelectionError::MaxInputValueExceeded {
+                target: self.target,
+                max_input_value: self.max_input_value.unwrap_or(Amount::MAX),
+            },
+            SelectionError::MaxInputCountExceeded { .. } => SelectionError::MaxInputCountExceeded {
+                max_input_count: self.max_input_count,
+            },
+            e => e,
+        })?;
+
+        selection.extend(report.selection.iter().map(|c| {
+            let (index, utxo) = rest[c.index];
+            candidate::Candidate {
+                index,
+                utxo,
+                effective_value: c.effective_value,
+                weight: c.weight,
+            }
+        }));
+        order::sort_candidates(&mut selection);
+        Ok(SelectionReport {
+            sampled: report.sampled,
+            limit_hit: report.limit_hit,
+            change_target: report.change_target,
+            ..SelectionReport::new(selection, self.target, report.iterations)?
+        })
+    }
+
+    /// Selects coins for [`select_with_rng`](Self::select_with_rng), without marking the
+    /// purpose.
+    fn select_for_purpose<R: rand::Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<SelectionReport<'a, U>, SelectionError> {
+        let strategy = self
+            .strategy
+            .unwrap_or_else(|| self.policy.strategy(self.fee_rate));
+        let min_change = match self.purpose {
+            Purpose::Payment => self.min_change,
+            Purpose::SelfTransfer => Amount::ZERO,
+        };
+        let report = match (strategy, self.purpose) {
+            (policy::Strategy::MinimizeWeight, _) => match coin_grinder::select_coins_coin_grinder(
+                self.target,
+                min_change,
+                self.max_weight,
+                self.max_input_count,
+                self.selection_fee_rate(),
+                self.on_limit,
+                self.pool,
+            ) {
+                Err(SelectionError::IterationLimitReached)
+                    if self.on_limit == coin_grinder::LimitPolicy::ReturnError =>
+                {
+                    return Err(SelectionError::IterationLimitReached);
+                }
+                report => report.ok(),
+            },
+            (_, Purpose::SelfTransfer) => consolidate::select_coins_consolidate(
+                self.target,
+                self.selection_fee_rate(),
+                self.pool,
+            )
+            .ok(),
+            (policy::Strategy::Consolidate, Purpose::Payment) => {
+                // The smallest candidates must leave the minimum change as well.
+                let consolidated = self.target.checked_add(min_change).and_then(|required| {
+                    consolidate::select_coins_consolidate(
+                        required,
+                        self.selection_fee_rate(),
+                        self.pool,
+                    )
+                    .ok()
+                });
+                match consolidated {
+                    Some(report) => Some(SelectionReport::new(report.selection, self.target, 0)?),
+                    None => None,
+                }
+            }
+            (policy::Strategy::Standard, Purpose::Payment) => None,
+        };
+        let within_cap = |report: &SelectionReport<'a, U>| {
+            self.max_input_value
+                .map_or(true, |max| report.input_value <= max)
+                && report.selection.len() <= self.max_input_count
+        };
+        let within_max_change = |report: &SelectionReport<'a, U>| {
+            self.max_change.map_or(true, |max| report.excess <= max)
+        };
+        let mut too_much_change = None;
+        if let Some(mut report) = report.filter(within_cap) {
+            if within_max_change(&report) {
+                report.change_target = Some(min_change);
+                return Ok(report);
+            }
+            too_much_change = Some(report);
+        }
+        let knapsack = knapsack::select_coins_knapsack(
+            self.target,
+            min_change,
+            self.max_input_value,
+            self.selection_fee_rate(),
+            self.pool,
+            rng,
+        )
+        .and_then(|report| {
+            if report.selection.len() > self.max_input_count {
+                Err(SelectionError::MaxInputCountExceeded {
+                    max_input_count: self.max_input_count,
+                })
+            } else {
+                Ok(report)
+            }
+        });
+        let mut report = match (knapsack, too_much_change) {
+            (Ok(report), Some(fallback)) if !within_max_change(&report) => fallback,
+            (Err(_), Some(fallback)) => fallback,
+            (knapsack, _) => knapsack?,
+        };
+        report.change_target = Some(min_change);
+        Ok(report)
+    }
+
+    /// Selects coins like [`select_with_rng`](Self::select_with_rng), using the thread
+    /// local random number generator.
+    pub fn select(&self) -> Result<SelectionReport<'a, U>, SelectionError> {
+        self.select_with_rng(&mut rand::thread_rng())
+    }
+}
+
+impl<'a, U: WeightedUtxo + UtxoMetadata> CoinSelector<'a, U> {
+    /// Selects coins like [`select_with_rng`](Self::select_with_rng), but spends every
+    /// candidate paying to the script of a selected one, like Bitcoin Core's
+    /// `-avoidpartialspends`.
+    ///
+    /// Spending part of the coins sent to an address links the rest to the transaction, so
+    /// the eligible candidates are [grouped by script](group::group_by_script) and whole
+    /// groups are selected. A preset input brings its group along. Fees are computed per
+    /// group, so an input's effective value in the report includes its share of the
+    /// rounding. The report carries the [privacy score](SelectionReport::privacy).
+    pub fn select_avoiding_partial_spends_with_rng<R: rand::Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<SelectionReport<'a, U>, SelectionError> {
+        if self.target_kind.is_some() {
+            return self
+                .resolve_target()?
+                .select_avoiding_partial_spends_with_rng(rng);
+        }
+        let mut rejections = Vec::new();
+        let candidates =
+            candidate::eligible_candidates(self.pool, self.selection_fee_rate(), |rejection| {
+                rejections.push(rejection)
+            });
+        let members: Vec<&'a U> = candidates.iter().map(|c| c.utxo).collect();
+        let groups = group::group_by_script(&members).ok_or(SelectionError::Overflow)?;
+
+        let mut preset_groups = Vec::with_capacity(self.preset_inputs.len());
+        for &index in self.preset_inputs {
+            let group = groups
+                .iter()
+                .position(|group| {
+                    group
+                        .utxos()
+                        .iter()
+                        .any(|&(member, _)| candidates[member].index == index)
+                })
+                .ok_or(SelectionError::InvalidPresetInput(index))?;
+            preset_groups.push(group);
+        }
+
+        let selector = CoinSelector {
+            pool: &groups,
+            target: self.target,
+            target_kind: None,
+            fee_rate: self.fee_rate,
+            min_change: self.min_change,
+            max_input_value: self.max_input_value,
+            max_weight: self.max_weight,
+            max_input_count: usize::MAX,
+            max_change: self.max_change,
+            policy: self.policy,
+            strategy: self.strategy,
+            purpose: self.purpose,
+            preset_inputs: &preset_groups,
+            on_limit: self.on_limit,
+            random_change_fee: self.random_change_fee,
+            max_fee_multiple: self.max_fee_multiple,
+            min_effective_value: self.min_effective_value,
+            subtract_fee_from_outputs: self.subtract_fee_from_outputs,
+            change_script: self.change_script,
+            change_output: self.change_output,
+            discard_fee_rate: self.discard_fee_rate,
+            dust: self.dust,
+            change_denominations: self.change_denominations,
+            standardness: self.standardness,
+        };
+        let report = selector.select_with_rng(rng)?;
+
+        let mut selection = Vec::new();
+        for group in &report.selection {
+            let start = selection.len();
+            let mut effective_value = Amount::ZERO;
+            for &(member, _) in group.utxo.utxos() {
+                let candidate = candidates[member];
+                effective_value = effective_value
+                    .checked_add(candidate.effective_value)
+                    .ok_or(SelectionError::Overflow)?;
+                selection.push(candidate);
+            }
+            // The group pays its fee once, on its total weight.
+            if let Some(rounding) = group.effective_value.checked_sub(effective_value) {
+                let first = &mut selection[start];
+                first.effective_value = first
+                    .effective_value
+                    .checked_add(rounding)
+                    .ok_or(SelectionError::Overflow)?;
+            }
+        }
+        if selection.len() > self.max_input_count {
+            return Err(SelectionError::MaxInputCountExceeded {
+                max_input_count: self.max_input_count,
+            });
+        }
+        order::sort_candidates(&mut selection);
+        let mut report = self.check_fee(SelectionReport {
+            sampled: report.sampled,
+            limit_hit: report.limit_hit,
+            change_target: report.change_target,
+            purpose: report.purpose,
+            ..SelectionReport::new(selection, self.target, report.iterations)?
+        })?;
+        report.change = self.change(&report)?;
+        self.check_standardness(&report)?;
+        report.rejections = rejections;
+        report.privacy = Some(self.privacy_score(&report));
+        Ok(report)
+    }
+
+    /// Selects coins like
+    /// [`select_avoiding_partial_spends_with_rng`](Self::select_avoiding_partial_spends_with_rng),
+    /// using the thread local random number generator.
+    pub fn select_avoiding_partial_spends(&self) -> Result<SelectionReport<'a, U>, SelectionError> {
+        self.select_avoiding_partial_spends_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Selects coins like [`select_with_rng`](Self::select_with_rng) and scores the
+    /// privacy of the selection, see [`SelectionReport::privacy`].
+    pub fn select_with_privacy_score_with_rng<R: rand::Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<SelectionReport<'a, U>, SelectionError> {
+        let mut report = self.select_with_rng(rng)?;
+        report.privacy = Some(self.privacy_score(&report));
+        Ok(report)
+    }
+
+    /// Selects coins like
+    /// [`select_with_privacy_score_with_rng`](Self::select_with_privacy_score_with_rng),
+    /// using the thread local random number generator.
+    pub fn select_with_privacy_score(&self) -> Result<SelectionReport<'a, U>, SelectionError> {
+        self.select_with_privacy_score_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Scores `report`, a selection from the pool, against the rest of the pool.
+    fn privacy_score(&self, report: &SelectionReport<'a, U>) -> privacy::PrivacyScore {
+        let selection: Vec<usize> = report.selection.iter().map(|c| c.index).collect();
+        privacy::privacy_score(&selection, self.pool, report.change)
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use super::*;
+
+    /// Minimal candidate used throughout the tests.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+    pub(crate) struct Utxo {
+        pub(crate) value: Amount,
+        pub(crate) satisfaction_weight: Weight,
+    }
+
+    impl WeightedUtxo for Utxo {
+        fn satisfaction_weight(&self) -> Weight {
+            self.satisfaction_weight
+        }
+        fn value(&self) -> Amount {
+            self.value
+        }
+    }
+
+    /// Builds a pool from `(value in sats, satisfaction weight in wu)` pairs.
+    pub(crate) fn build_pool(utxos: &[(u64, u64)]) -> Vec<Utxo> {
+        utxos
+            .iter()
+            .map(|&(value, weight)| Utxo {
+                value: Amount::from_sat(value),
+                satisfaction_weight: Weight::from_wu(weight),
+            })
+            .collect()
+    }
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn effective_value_includes_base_weight() {
+        let utxo = &build_pool(&[(10_000, 272)])[0];
+        let fee_rate = FeeRate::from_sat_per_kwu(10);
+
+        assert_eq!(utxo.weight(), Some(Weight::from_wu(432)));
+        assert_eq!(
+            utxo.effective_value(fee_rate),
+            Some(SignedAmount::from_sat(10_000 - 5))
+        );
+    }
+
+    #[test]
+    fn effective_value_can_be_negative() {
+        let utxo = &build_pool(&[(100, 272)])[0];
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+
+        assert_eq!(
+            utxo.effective_value(fee_rate),
+            Some(SignedAmount::from_sat(100 - 1080))
+        );
+    }
+
+    #[test]
+    fn references_are_candidates() {
+        let pool = build_pool(&[(3_000, 272), (1_000, 272)]);
+        let refs: Vec<&Utxo> = pool.iter().rev().collect();
+        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+
+        let candidates = candidate::eligible_candidates(&refs, fee_rate, |_| {});
+        assert_eq!(candidates[0].effective_value, Amount::from_sat(892));
+    }
+
+    #[test]
+    fn selection_error_display() {
+        let e = SelectionError::InsufficientFunds {
+            available: Amount::from_sat(6_000),
+            required: Amount::from_sat(10_000),
+        };
+        assert_eq!(
+            e.to_string(),
+            "insufficient funds: 0.00006000 BTC available, 0.00010000 BTC required"
+        );
+
+        let e = SelectionError::from(target::InvalidTargetError::Zero);
+        assert_eq!(e.to_string(), "invalid target: target is zero");
+
+        let e = SelectionError::InvalidPresetInput(3);
+        assert_eq!(e.to_string(), "preset input 3 can't be selected");
+
+        let e = SelectionError::MaxInputCountExceeded { max_input_count: 2 };
+        assert_eq!(
+            e.to_string(),
+            "every selection has more inputs than the maximum of 2"
+        );
+
+        let e = SelectionError::FeeExceedsPayment {
+            fee: Amount::from_sat(2),
+            max_fee: Amount::from_sat(1),
+        };
+        assert_eq!(
+            e.to_string(),
+            "fee of 0.00000002 BTC exceeds the maximum of 0.00000001 BTC for the payment"
+        );
+    }
+
+    #[test]
+    fn selection_report() {
+        let pool = build_pool(&[(10_000, 272), (5_000, 272)]);
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        let selection = candidate::eligible_candidates(&pool, fee_rate, |_| {});
+
+        let report = SelectionReport::new(selection, Amount::from_sat(12_000), 3).unwrap();
+        assert_eq!(report.input_value, Amount::from_sat(15_000));
+        assert_eq!(report.effective_value, Amount::from_sat(15_000 - 2 * 1_080));
+        assert_eq!(report.weight, Weight::from_wu(2 * 432));
+        assert_eq!(report.fee, Amount::from_sat(2 * 1_080));
+        assert_eq!(report.excess, Amount::from_sat(840));
+        assert_eq!(report.iterations, 3);
+
+        // Spending at 10 sat/vB instead of 1 sat/vB later costs 2 * (1 080 - 108).
+        let long_term_fee_rate = FeeRate::from_sat_per_vb_u32(1);
+        assert_eq!(
+            report.waste(long_term_fee_rate, None),
+            Some(SignedAmount::from_sat(1_944 + 840))
+        );
+        assert_eq!(
+            report.waste(long_term_fee_rate, Some(Amount::from_sat(500))),
+            Some(SignedAmount::from_sat(1_944 + 500))
+        );
+        let estimator = estimator::StaticFeeEstimator {
+            fee_rate,
+            long_term_fee_rate,
+        };
+        assert_eq!(
+            report.waste(estimator, None),
+            report.waste(long_term_fee_rate, None)
+        );
+    }
+
+    #[test]
+    fn selection_report_below_target() {
+        let pool = build_pool(&[(10_000, 0)]);
+        let selection = candidate::eligible_candidates(&pool, FeeRate::ZERO, |_| {});
+        assert_eq!(
+            SelectionReport::new(selection, Amount::from_sat(10_001), 0).map(|r| r.excess),
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(10_000),
+                required: Amount::from_sat(10_001),
+            })
+        );
+    }
+
+    #[test]
+    fn coin_selector() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let pool = build_pool(&[(50_000, 0), (30_000, 0), (25_000, 0), (10_000, 0)]);
+        // Knapsack is used at any fee rate up to the default threshold for minimizing weight.
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(55_000))
+            .min_change(Amount::from_sat(1_000))
+            .policy(policy::AutoPolicy::new(
+                defaults::MINIMIZE_WEIGHT_ABOVE,
+                FeeRate::ZERO,
+            ));
+
+        let report = selector
+            .select_with_rng(&mut StdRng::seed_from_u64(1))
+            .unwrap();
+        let indices: Vec<usize> = report.selection.iter().map(|c| c.index).collect();
+        assert_eq!(indices, vec![1, 2]);
+
+        let report = selector.fee_rate(FeeRate::from_sat_per_vb_u32(1)).select();
+        assert!(report.unwrap().excess >= Amount::from_sat(1_000));
+
+        assert_eq!(
+            selector
+                .target(Amount::from_sat(115_001))
+                .select()
+                .map(|r| r.excess),
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(115_000),
+                required: Amount::from_sat(115_001),
+            })
+        );
+        assert_eq!(
+            selector.target(Amount::ZERO).select().map(|r| r.excess),
+            Err(SelectionError::InvalidTarget(
+                target::InvalidTargetError::Zero
+            ))
+        );
+    }
+
+    #[test]
+    fn coin_selector_target_kind() {
+        use core::convert::TryFrom;
+
+        let pool = build_pool(&[(60_000, 0), (40_000, 0)]);
+        // Header of a segwit transaction with one P2WPKH output: 10.5 + 31 vB.
+        let recipients = target::TargetKind::RecipientsPlusOverhead {
+            outputs: Amount::from_sat(59_800),
+            base_weight: Weight::from_wu(42 + 124),
+        };
+        let selector = CoinSelector::new(&pool)
+            .target_kind(recipients)
+            .fee_rate(FeeRate::from_sat_per_vb_u32(10))
+            .min_change(Amount::from_sat(1_000))
+            .policy(policy::AutoPolicy::new(
+                defaults::MINIMIZE_WEIGHT_ABOVE,
+                FeeRate::ZERO,
+            ));
+
+        // With the 415 sats for the header and output, the 59 600 sats of the larger
+        // candidate don't cover the target.
+        let report = selector.select().unwrap();
+        assert_eq!(report.selection.len(), 2);
+        assert_eq!(report.excess, Amount::from_sat(99_200 - 60_215));
+        let report = selector.subtract_fee_from_outputs(true).select().unwrap();
+        assert_eq!(report.excess, Amount::from_sat(100_000 - 59_800));
+
+        let signed = target::TargetKind::try_from(SignedAmount::from_sat(-1_000));
+        assert_eq!(
+            signed.map_err(SelectionError::from),
+            Err(SelectionError::InvalidTarget(
+                target::InvalidTargetError::Negative(SignedAmount::from_sat(-1_000))
+            ))
+        );
+    }
+
+    #[test]
+    fn coin_selector_consolidates_payments_at_low_fee_rates() {
+        let pool = build_pool(&[(50_000, 0), (1_000, 0), (3_000, 0), (20_000, 0)]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(10_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(1))
+            .min_change(Amount::from_sat(1_000));
+        let indices = |selector: CoinSelector<'_, Utxo>| -> Vec<usize> {
+            let report = selector.select().unwrap();
+            report.selection.iter().map(|c| c.index).collect()
+        };
+
+        // Each input costs 40 sats, so the 960 and 2 960 sats don't leave 1 000 of change
+        // on their own.
+        assert_eq!(indices(selector), vec![3, 2, 1]);
+        assert_eq!(
+            indices(selector.fee_rate(FeeRate::from_sat_per_vb_u32(3))),
+            vec![3]
+        );
+    }
+
+    #[test]
+    fn coin_selector_minimizes_weight_at_high_fee_rates() {
+        let pool = build_pool(&[(3_000_000, 1_000), (1_500_000, 112), (1_500_000, 112)]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(2_500_000))
+            .min_change(Amount::from_sat(10_000));
+        let indices = |fee_rate| -> Vec<usize> {
+            let report = selector.fee_rate(fee_rate).select().unwrap();
+            report.selection.iter().map(|c| c.index).collect()
+        };
+
+        // Knapsack prefers the single candidate with the lower effective value.
+        assert_eq!(indices(FeeRate::from_sat_per_vb_u32(3)), vec![0]);
+        assert_eq!(indices(FeeRate::from_sat_per_vb_u32(30)), vec![0]);
+        // CoinGrinder picks the two lighter candidates.
+        assert_eq!(indices(FeeRate::from_sat_per_vb_u32(50)), vec![1, 2]);
+    }
+
+    #[test]
+    fn coin_selector_estimated_fee_rate() {
+        struct Estimates;
+        impl estimator::FeeEstimator for Estimates {
+            fn fee_rate(&self, conf_target: u16) -> FeeRate {
+                if conf_target <= 2 {
+                    FeeRate::from_sat_per_vb_u32(50)
+                } else {
+                    FeeRate::from_sat_per_vb_u32(3)
+                }
+            }
+        }
+        impl estimator::LongTermFeeEstimator for Estimates {
+            fn long_term_fee_rate(&self) -> FeeRate {
+                FeeRate::from_sat_per_vb_u32(3)
+            }
+        }
+
+        let pool = build_pool(&[(3_000_000, 1_000), (1_500_000, 112), (1_500_000, 112)]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(2_500_000))
+            .min_change(Amount::from_sat(10_000));
+        let indices = |conf_target| -> Vec<usize> {
+            let report = selector
+                .estimated_fee_rate(conf_target, Estimates)
+                .select()
+                .unwrap();
+            report.selection.iter().map(|c| c.index).collect()
+        };
+
+        assert_eq!(indices(1), vec![1, 2]);
+        assert_eq!(indices(6), vec![0]);
+    }
+
+    #[test]
+    fn coin_selector_conf_target() {
+        let pool = build_pool(&[(3_000_000, 1_000), (1_500_000, 112), (1_500_000, 112)]);
+        let estimator = estimator::StaticFeeEstimator {
+            fee_rate: FeeRate::from_sat_per_vb_u32(3),
+            long_term_fee_rate: FeeRate::from_sat_per_vb_u32(3),
+        };
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(2_500_000))
+            .min_change(Amount::from_sat(10_000));
+        let indices = |selector: CoinSelector<'_, Utxo>| -> Vec<usize> {
+            let report = selector.select().unwrap();
+            report.selection.iter().map(|c| c.index).collect()
+        };
+
+        // An urgent payment minimizes weight even at 3 sat/vB, others leave it to the fee
+        // rate.
+        let policy = policy::ConfTargetPolicy::default();
+        assert_eq!(
+            indices(selector.conf_target(1, policy, estimator)),
+            vec![1, 2]
+        );
+        assert_eq!(indices(selector.conf_target(6, policy, estimator)), vec![0]);
+        assert_eq!(
+            indices(
+                selector
+                    .conf_target(1, policy, estimator)
+                    .policy(policy::AutoPolicy::default())
+            ),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn coin_selector_max_input_count() {
+        let pool = build_pool(&[(30_000, 0), (30_000, 0), (30_000, 0), (100_000, 0)]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(80_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(50))
+            .min_change(Amount::ZERO)
+            .max_input_count(2);
+        let input_count =
+            |selector: CoinSelector<'_, Utxo>| selector.select().map(|r| r.selection.len());
+
+        assert_eq!(input_count(selector), Ok(1));
+        assert_eq!(input_count(selector.preset_inputs(&[0])), Ok(2));
+        assert_eq!(
+            input_count(selector.preset_inputs(&[0, 1, 2])),
+            Err(SelectionError::MaxInputCountExceeded { max_input_count: 2 })
+        );
+    }
+
+    #[test]
+    fn coin_selector_randomizes_change_target() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let pool = build_pool(&[(10_000_000, 272); 4]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(12_000_000))
+            .randomize_change_target(Amount::from_sat(1_000));
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let report = selector.select_with_rng(&mut rng).unwrap();
+        let change_target = report.change_target.unwrap();
+        assert!(change_target >= srd::CHANGE_LOWER + Amount::from_sat(1_000));
+        assert!(change_target < srd::CHANGE_UPPER + Amount::from_sat(1_000));
+        assert_ne!(change_target, knapsack::DEFAULT_MIN_CHANGE);
+
+        let report = selector.select_with_rng(&mut rng).unwrap();
+        assert_ne!(report.change_target, Some(change_target));
+    }
+
+    #[test]
+    fn coin_selector_avoids_partial_spends() {
+        use bitcoin::hashes::Hash;
+        use bitcoin::{ScriptBuf, TxOut, WPubkeyHash};
+
+        let utxo = |value, key| {
+            let txout = TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([key; 20])),
+            };
+            utxo::Utxo::new(OutPoint::null(), txout, Weight::from_wu(108))
+        };
+        let pool = vec![utxo(45_000, 1), utxo(5_000, 1), utxo(60_000, 2)];
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(40_000))
+            .min_change(Amount::ZERO)
+            .policy(policy::AutoPolicy::new(
+                defaults::MINIMIZE_WEIGHT_ABOVE,
+                FeeRate::ZERO,
+            ));
+        let indices = |report: SelectionReport<'_, utxo::Utxo>| -> Vec<usize> {
+            report.selection.iter().map(|c| c.index).collect()
+        };
+
+        assert_eq!(selector.select().map(indices), Ok(vec![0]));
+        assert_eq!(
+            selector.select_avoiding_partial_spends().map(indices),
+            Ok(vec![0, 1])
+        );
+        let reused_scripts = |report: SelectionReport<'_, utxo::Utxo>| {
+            report.privacy.map(|privacy| privacy.reused_scripts)
+        };
+        assert_eq!(selector.select().map(|r| r.privacy), Ok(None));
+        assert_eq!(
+            selector.select_with_privacy_score().map(reused_scripts),
+            Ok(Some(1))
+        );
+        assert_eq!(
+            selector
+                .select_avoiding_partial_spends()
+                .map(reused_scripts),
+            Ok(Some(0))
+        );
+        assert_eq!(
+            selector
+                .preset_inputs(&[1])
+                .select_avoiding_partial_spends()
+                .map(indices),
+            Ok(vec![0, 1])
+        );
+        assert_eq!(
+            selector
+                .preset_inputs(&[2])
+                .select_avoiding_partial_spends()
+                .map(indices),
+            Ok(vec![2])
+        );
+        assert_eq!(
+            selector
+                .preset_inputs(&[3])
+                .select_avoiding_partial_spends()
+                .map(indices),
+            Err(SelectionError::InvalidPresetInput(3))
+        );
+        let report = selector
+            .target_kind(target::TargetKind::Effective(Amount::from_sat(55_000)))
+            .select_avoiding_partial_spends()
+            .unwrap();
+        assert_eq!(report.excess, Amount::from_sat(5_000));
+        assert_eq!(indices(report), vec![2]);
+    }
+
+    #[test]
+    fn coin_selector_change_script() {
+        use bitcoin::hashes::Hash;
+        use bitcoin::{ScriptBuf, WPubkeyHash, WScriptHash};
+
+        // Each input costs 400 sats at 10 sat/vB, a P2WPKH change output 310.
+        let pool = build_pool(&[(51_700, 0), (100_000, 0)]);
+        let change_script = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(50_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(10))
+            .min_change(Amount::from_sat(1_000))
+            .policy(policy::AutoPolicy::new(
+                defaults::MINIMIZE_WEIGHT_ABOVE,
+                FeeRate::ZERO,
+            ));
+        let indices = |report: &SelectionReport<'_, Utxo>| -> Vec<usize> {
+            report.selection.iter().map(|c| c.index).collect()
+        };
+
+        let report = selector.select().unwrap();
+        assert_eq!(indices(&report), vec![0]);
+        assert_eq!(report.change, None);
+
+        // 1 300 sats of excess don't pay for the change output and leave 1 000.
+        let selector = selector.change_script(&change_script);
+        let report = selector.select().unwrap();
+        assert_eq!(indices(&report), vec![1]);
+        assert_eq!(report.change, Some(Amount::from_sat(49_600 - 310)));
+        // Spending the change costs 680 sats at the discard fee rate.
+        assert_eq!(
+            selector.waste(&report, FeeRate::from_sat_per_vb_u32(10)),
+            Some(SignedAmount::from_sat(310 + 680))
+        );
+        assert_eq!(
+            selector
+                .discard_fee_rate(FeeRate::from_sat_per_vb_u32(20))
+                .waste(&report, FeeRate::from_sat_per_vb_u32(10)),
+            Some(SignedAmount::from_sat(310 + 1_360))
+        );
+
+        let unsupported = ScriptBuf::new_p2wsh(&WScriptHash::all_zeros());
+        assert_eq!(
+            selector
+                .change_script(&unsupported)
+                .select()
+                .map(|r| r.change),
+            Err(SelectionError::UnsupportedChangeScript)
+        );
+
+        // A P2WSH change output weighs 172 wu and costs 430 sats.
+        let p2wsh = change::ChangeOutput::new(Weight::from_wu(172), Weight::from_wu(300)).unwrap();
+        let selector = selector.change_script(&unsupported).change_output(p2wsh);
+        let report = selector.select().unwrap();
+        assert_eq!(report.change, Some(Amount::from_sat(49_600 - 430)));
+        // Spending it costs 40 + 75 vB at the discard fee rate.
+        assert_eq!(
+            selector.waste(&report, FeeRate::from_sat_per_vb_u32(10)),
+            Some(SignedAmount::from_sat(430 + 1_150))
+        );
+    }
+
+    #[test]
+    fn coin_selector_reports_rejections() {
+        use candidate::{RejectReason, Rejection};
+
+        // Each input costs 1 080 sats at 10 sat/vB.
+        let pool = build_pool(&[(10_000, 272), (1_000, 272), (100, 272), (2_000, 272)]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(5_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(10))
+            .min_change(Amount::ZERO)
+            .min_effective_value(Amount::from_sat(5_000));
+        let non_positive = |index, value| Rejection {
+            index,
+            reason: RejectReason::NonPositiveEffectiveValue(SignedAmount::from_sat(value)),
+        };
+
+        assert_eq!(
+            selector.select().unwrap().rejections,
+            vec![
+                non_positive(1, -80),
+                non_positive(2, -980),
+                Rejection {
+                    index: 3,
+                    reason: RejectReason::BelowFloor {
+                        effective_value: Amount::from_sat(920),
+                        floor: Amount::from_sat(5_000),
+                    },
+                },
+            ]
+        );
+        // The preset input is spent despite the floor.
+        assert_eq!(
+            selector.preset_inputs(&[3]).select().unwrap().rejections,
+            vec![non_positive(1, -80), non_positive(2, -980)]
+        );
+    }
+
+    #[test]
+    fn coin_selector_standardness() {
+        use bitcoin::hashes::Hash;
+        use bitcoin::{ScriptBuf, WPubkeyHash};
+
+        use crate::standardness::{StandardnessError, StandardnessLimits};
+
+        let pool = build_pool(&[(20_000, 112), (20_000, 112), (20_000, 112)]);
+        let script = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        let output = |value| TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: script.clone(),
+        };
+        let payment = [output(50_000)];
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(50_000))
+            .fee_rate(FeeRate::ZERO)
+            .min_change(Amount::ZERO);
+
+        let report = selector
+            .standardness(StandardnessLimits::default(), &payment)
+            .select()
+            .unwrap();
+        assert_eq!(report.selection.len(), 3);
+
+        let limits = StandardnessLimits {
+            max_inputs: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            selector.standardness(limits, &payment).select().map(|_| ()),
+            Err(SelectionError::NonStandard(
+                StandardnessError::TooManyInputs { count: 3, max: 2 }
+            ))
+        );
+
+        let dust_payment = [output(293)];
+        assert_eq!(
+            selector
+                .target(Amount::from_sat(293))
+                .standardness(StandardnessLimits::default(), &dust_payment)
+                .select()
+                .map(|_| ()),
+            Err(SelectionError::NonStandard(StandardnessError::DustOutput {
+                index: 0,
+                value: Amount::from_sat(293),
+                threshold: Amount::from_sat(294),
+            }))
+        );
+
+        // The 10 000 sats of change add an output to the transaction.
+        let max_weight = predict_transaction_weight(&pool, &payment, false).unwrap();
+        let limits = StandardnessLimits {
+            max_weight,
+            ..Default::default()
+        };
+        assert!(selector.standardness(limits, &payment).select().is_ok());
+        assert_eq!(
+            selector
+                .change_script(&script)
+                .standardness(limits, &payment)
+                .select()
+                .map(|_| ()),
+            Err(SelectionError::NonStandard(
+                StandardnessError::ExcessiveWeight {
+                    weight: Some(max_weight + Weight::from_wu(124)),
+                    max: max_weight,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn coin_selector_dust_change() {
+        use bitcoin::hashes::Hash;
+        use bitcoin::{ScriptBuf, WPubkeyHash};
+
+        // Each input costs 400 sats at 10 sat/vB, a P2WPKH change output 310 and its dust
+        // threshold is 294 sats.
+        let pool = build_pool(&[(51_000, 0), (100_000, 0)]);
+        let change_script = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        let selector = |pool| {
+            CoinSelector::new(pool)
+                .target(Amount::from_sat(50_000))
+                .fee_rate(FeeRate::from_sat_per_vb_u32(10))
+                .min_change(Amount::ZERO)
+                .policy(policy::AutoPolicy::new(
+                    defaults::MINIMIZE_WEIGHT_ABOVE,
+                    FeeRate::ZERO,
+                ))
+                .change_script(&change_script)
+        };
+        let change = |selector: CoinSelector<'_, Utxo>| {
+            let report = selector.select().unwrap();
+            (report.selection[0].index, report.change)
+        };
+
+        // The 600 sats of excess of the smaller candidate would leave 290 of dust change.
+        assert_eq!(
+            change(selector(&pool)),
+            (1, Some(Amount::from_sat(100_000 - 400 - 50_000 - 310)))
+        );
+        assert_eq!(change(selector(&pool[..1])), (0, None));
+
+        let relaxed = dust::CoreDust {
+            dust_relay_fee: FeeRate::from_sat_per_vb_u32(1),
+        };
+        assert_eq!(
+            change(selector(&pool).dust(&relaxed)),
+            (0, Some(Amount::from_sat(290)))
+        );
+
+        struct Flat(u64);
+        impl DustCalculator for Flat {
+            fn dust_threshold(&self, _: &Script) -> Amount {
+                Amount::from_sat(self.0)
+            }
+        }
+        assert_eq!(
+            change(selector(&pool).dust(&Flat(100))),
+            (0, Some(Amount::from_sat(290)))
+        );
+        assert_eq!(change(selector(&pool[..1]).dust(&Flat(291))), (0, None));
+    }
+
+    #[test]
+    fn coin_selector_change_denominations() {
+        let pool = build_pool(&[(60_000, 0), (90_000, 0)]);
+        let ladder =
+            change::DenominationLadder::new([Amount::from_sat(10_000), Amount::from_sat(50_000)]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(30_000))
+            .min_change(Amount::from_sat(15_000))
+            .policy(policy::AutoPolicy::new(
+                defaults::MINIMIZE_WEIGHT_ABOVE,
+                FeeRate::ZERO,
+            ));
+        let report = selector.select().unwrap();
+        assert_eq!(report.selection[0].index, 0);
+        assert_eq!(report.change, None);
+
+        // Change of 30 000 would round down to 10 000, below the minimum change.
+        let selector = selector.change_denominations(&ladder);
+        let report = selector.select().unwrap();
+        assert_eq!(report.selection[0].index, 1);
+        assert_eq!(report.change, Some(Amount::from_sat(50_000)));
+        assert_eq!(
+            selector.waste(&report, FeeRate::ZERO),
+            Some(SignedAmount::from_sat(10_000))
+        );
+    }
+
+    #[test]
+    fn coin_selector_max_change() {
+        let pool = build_pool(&[(10_000_000, 0), (300_000, 0), (300_000, 0)]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(500_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(50))
+            .min_change(Amount::ZERO);
+        let indices = |selector: CoinSelector<'_, Utxo>| -> Vec<usize> {
+            let report = selector.select().unwrap();
+            report.selection.iter().map(|c| c.index).collect()
+        };
+
+        // The lightest selection leaves 9.5M sats of change, knapsack much less.
+        assert_eq!(indices(selector), vec![0]);
+        assert_eq!(indices(selector.max_change(srd::CHANGE_UPPER)), vec![1, 2]);
+        // If every selection leaves too much, the lightest is still returned.
+        assert_eq!(
+            indices(selector.max_change(Amount::from_sat(1_000))),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn coin_selector_max_fee_multiple() {
+        let pool = build_pool(&[(1_000_000, 272)]);
+        // 5 000 sat/kvB mistaken for sat/vB makes the input cost 540 000 sats.
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(100_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(5_000));
+
+        assert_eq!(
+            selector.select().map(|_| ()),
+            Err(SelectionError::FeeExceedsPayment {
+                fee: Amount::from_sat(540_000),
+                max_fee: Amount::from_sat(100_000),
+            })
+        );
+        assert!(selector.max_fee_multiple(Some(6)).select().is_ok());
+        assert!(selector.max_fee_multiple(None).select().is_ok());
+        assert!(selector.purpose(Purpose::SelfTransfer).select().is_ok());
+    }
+
+    #[test]
+    fn coin_selector_min_effective_value() {
+        let pool = build_pool(&[(40_000, 0), (500, 0), (500, 0), (30_000, 0)]);
+        let preset = [1];
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(60_000))
+            .min_change(Amount::ZERO)
+            .min_effective_value(Amount::from_sat(1_000));
+
+        // The preset input is spent although it is below the floor, the other isn't.
+        let report = selector.preset_inputs(&preset).select().unwrap();
+        let indices: Vec<usize> = report.selection.iter().map(|c| c.index).collect();
+        assert_eq!(indices, vec![0, 3, 1]);
+
+        let selector = selector.min_effective_value(Amount::from_sat(35_000));
+        assert!(matches!(
+            selector.select(),
+            Err(SelectionError::InsufficientFunds { .. })
+        ));
+    }
+
+    #[test]
+    fn coin_selector_subtracts_fee_from_outputs() {
+        let pool = build_pool(&[(60_000, 272), (40_000, 272), (30_000, 272)]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(100_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(10))
+            .min_change(Amount::ZERO)
+            .policy(policy::AutoPolicy::new(FeeRate::ZERO, FeeRate::ZERO));
+
+        // Each input costs 1 080 sats, so the first two no longer cover the target.
+        let indices = |report: SelectionReport<'_, Utxo>| -> Vec<usize> {
+            report.selection.iter().map(|c| c.index).collect()
+        };
+        assert_eq!(indices(selector.select().unwrap()), vec![0, 1, 2]);
+
+        let report = selector.subtract_fee_from_outputs(true).select().unwrap();
+        assert_eq!(report.effective_value, report.input_value);
+        assert_eq!(report.fee, Amount::from_sat(2 * 1_080));
+        assert_eq!(indices(report), vec![0, 1]);
+    }
+
+    #[test]
+    fn coin_selector_subtracted_fee_includes_bump_fees() {
+        struct Unconfirmed(Utxo);
+        impl WeightedUtxo for Unconfirmed {
+            fn satisfaction_weight(&self) -> Weight {
+                self.0.satisfaction_weight
+            }
+            fn value(&self) -> Amount {
+                self.0.value
+            }
+            fn ancestor_fee(&self) -> Amount {
+                Amount::from_sat(150)
+            }
+            fn ancestor_weight(&self) -> Weight {
+                Weight::from_wu(600)
+            }
+        }
+
+        let pool: Vec<Unconfirmed> = build_pool(&[(60_000, 272)])
+            .into_iter()
+            .map(Unconfirmed)
+            .collect();
+        let report = CoinSelector::new(&pool)
+            .target(Amount::from_sat(50_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(10))
+            .min_change(Amount::ZERO)
+            .subtract_fee_from_outputs(true)
+            .select()
+            .unwrap();
+        // The 150 vB parent paid 150 sats and needs 1 500.
+        assert_eq!(report.fee, Amount::from_sat(1_080 + 1_350));
+    }
+
+    #[test]
+    fn coin_selector_iteration_limit() {
+        let pool: Vec<(u64, u64)> = (0..60).map(|i| (1_000_000 + i * 7, 272 + i % 3)).collect();
+        let pool = build_pool(&pool);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(30_000_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(50));
+
+        let report = selector.select().unwrap();
+        assert!(report.limit_hit);
+        assert_eq!(
+            selector
+                .on_iteration_limit(coin_grinder::LimitPolicy::ReturnError)
+                .select()
+                .map(|_| ()),
+            Err(SelectionError::IterationLimitReached)
+        );
+    }
+
+    #[test]
+    fn coin_selector_self_transfer() {
+        let pool = build_pool(&[(500_000, 0), (20_000, 0), (30_000, 0), (5_000, 0)]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(40_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(5))
+            .min_change(Amount::from_sat(20_000));
+
+        // The small candidates can't leave the minimum change.
+        let payment = selector.select().unwrap();
+        assert_eq!(payment.purpose, Purpose::Payment);
+        assert_eq!(payment.selection.len(), 1);
+        assert_eq!(payment.selection[0].index, 0);
+
+        // The small candidates are consolidated, without padding the change.
+        let transfer = selector.purpose(Purpose::SelfTransfer).select().unwrap();
+        assert_eq!(transfer.purpose, Purpose::SelfTransfer);
+        let indices: Vec<usize> = transfer.selection.iter().map(|c| c.index).collect();
+        assert_eq!(indices, vec![2, 1, 3]);
+
+        // At high fee rates weight still comes first.
+        let transfer = selector
+            .purpose(Purpose::SelfTransfer)
+            .fee_rate(FeeRate::from_sat_per_vb_u32(50))
+            .select()
+            .unwrap();
+        assert_eq!(transfer.purpose, Purpose::SelfTransfer);
+        assert_eq!(transfer.selection.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_impls() {
+        fn owned<T: serde::Serialize + serde::de::DeserializeOwned>() {}
+        fn borrowed<T: serde::Serialize>(_: &T) {}
+
+        owned::<utxo::Utxo>();
+        owned::<SelectionError>();
+        owned::<Purpose>();
+        owned::<policy::AutoPolicy>();
+        owned::<policy::ConfTargetPolicy>();
+        owned::<objective::Objective>();
+
+        let pool = build_pool(&[(10_000, 272)]);
+        let report = CoinSelector::new(&pool)
+            .target(Amount::from_sat(5_000))
+            .select()
+            .unwrap();
+        borrowed(&report);
+    }
+
+    #[test]
+    fn coin_selector_preset_inputs() {
+        let pool = build_pool(&[(50_000, 0), (20_000, 0), (30_000, 0), (5_000, 0), (10, 272)]);
+        let selector = CoinSelector::new(&pool)
+            .target(Amount::from_sat(40_000))
+            .min_change(Amount::from_sat(1_000))
+            .policy(policy::AutoPolicy::new(
+                defaults::MINIMIZE_WEIGHT_ABOVE,
+                FeeRate::ZERO,
+            ));
+        let select = |selector: CoinSelector<'_, Utxo>| {
+            selector
+                .select()
+                .map(|report| report.selection.iter().map(|c| c.index).collect::<Vec<_>>())
+        };
+
+        // The rest of the target is covered by the lowest larger candidate.
+        assert_eq!(select(selector.preset_inputs(&[3])), Ok(vec![0, 3]));
+        // The preset inputs cover the target alone.
+        assert_eq!(select(selector.preset_inputs(&[0, 0])), Ok(vec![0]));
+        assert_eq!(
+            select(
+                selector
+                    .preset_inputs(&[0])
+                    .target(Amount::from_sat(60_000))
+                    .max_input_value(Amount::from_sat(40_000))
+            ),
+            Err(SelectionError::MaxInputValueExceeded {
+                target: Amount::from_sat(60_000),
+                max_input_value: Amount::from_sat(40_000),
+            })
+        );
+        assert_eq!(
+            select(
+                selector
+                    .preset_inputs(&[1])
+                    .target(Amount::from_sat(200_000))
+            ),
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(105_010),
+                required: Amount::from_sat(200_000),
+            })
+        );
+
+        assert_eq!(
+            select(selector.preset_inputs(&[5])),
+            Err(SelectionError::InvalidPresetInput(5))
+        );
+        let selector = selector.fee_rate(FeeRate::from_sat_per_vb_u32(1));
+        assert_eq!(
+            select(selector.preset_inputs(&[1, 4])),
+            Err(SelectionError::InvalidPresetInput(4))
+        );
+    }
+
+    #[test]
+    fn fee_adjustment() {
+        struct Credited(Utxo, SignedAmount);
+        impl WeightedUtxo for Credited {
+            fn satisfaction_weight(&self) -> Weight {
+                self.0.satisfaction_weight
+            }
+            fn value(&self) -> Amount {
+                self.0.value
+            }
+            fn fee_adjustment(&self) -> SignedAmount {
+                self.1
+            }
+        }
+
+        // Each 432 wu input pays 1 080 sats at 10 sat/vB.
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        let pool: Vec<Credited> = build_pool(&[(10_000, 272), (10_000, 272)])
+            .into_iter()
+            .zip([SignedAmount::from_sat(1_500), SignedAmount::from_sat(-500)])
+            .map(|(utxo, adjustment)| Credited(utxo, adjustment))
+            .collect();
+        assert_eq!(
+            pool[0].effective_value(fee_rate),
+            Some(SignedAmount::from_sat(10_420))
+        );
+        assert_eq!(
+            <&Credited as WeightedUtxo>::effective_value(&&pool[1], fee_rate),
+            Some(SignedAmount::from_sat(8_420))
+        );
+
+        let candidates = candidate::eligible_candidates(&pool, fee_rate, |_| {});
+        let report = SelectionReport::new(candidates, Amount::from_sat(15_000), 0).unwrap();
+        assert_eq!(report.effective_value, Amount::from_sat(18_840));
+        assert_eq!(report.fee, Amount::from_sat(2 * 1_080));
+    }
+
+    #[test]
+    fn ancestor_bump_fee() {
+        struct Unconfirmed(Utxo, Amount);
+        impl WeightedUtxo for Unconfirmed {
+            fn satisfaction_weight(&self) -> Weight {
+                self.0.satisfaction_weight
+            }
+            fn value(&self) -> Amount {
+                self.0.value
+            }
+            fn ancestor_fee(&self) -> Amount {
+                self.1
+            }
+            fn ancestor_weight(&self) -> Weight {
+                Weight::from_wu(600)
+            }
+        }
+
+        // The 150 vB parents need 1 500 sats at 10 sat/vB, the first paid 150.
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        let pool: Vec<Unconfirmed> = build_pool(&[(10_000, 272), (10_000, 272)])
+            .into_iter()
+            .zip(vec![Amount::from_sat(150), Amount::from_sat(2_000)])
+            .map(|(utxo, ancestor_fee)| Unconfirmed(utxo, ancestor_fee))
+            .collect();
+        assert_eq!(pool[0].bump_fee(fee_rate), Some(Amount::from_sat(1_350)));
+        assert_eq!(
+            pool[0].bump_fee(FeeRate::from_sat_per_vb_u32(1)),
+            Some(Amount::ZERO)
+        );
+        assert_eq!(pool[1].bump_fee(fee_rate), Some(Amount::ZERO));
+        assert_eq!(
+            pool[0].effective_value(fee_rate),
+            Some(SignedAmount::from_sat(7_570))
+        );
+
+        let candidates = candidate::eligible_candidates(&pool, fee_rate, |_| {});
+        let report = SelectionReport::new(candidates, Amount::from_sat(15_000), 0).unwrap();
+        assert_eq!(report.effective_value, Amount::from_sat(7_570 + 8_920));
+        assert_eq!(report.fee, Amount::from_sat(2 * 1_080 + 1_350));
+    }
+
+    #[test]
+    fn effective_value_overflow() {
+        let utxo = &build_pool(&[(100, u64::MAX)])[0];
+        assert_eq!(utxo.effective_value(FeeRate::ZERO), None);
+
+        let utxo = &build_pool(&[(u64::MAX, 0)])[0];
+        assert_eq!(utxo.effective_value(FeeRate::ZERO), None);
+
+        let utxo = &build_pool(&[(100, 0)])[0];
+        assert_eq!(utxo.effective_value(FeeRate::MAX), None);
+    }
 }
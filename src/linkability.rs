@@ -0,0 +1,154 @@
+//! Linkability analysis of proposed transactions.
+//!
+//! Chain analysis links inputs and outputs with simple heuristics. This module computes
+//! some of them for a proposed transaction so alternative selections can be compared by
+//! how much they give away. The analysis is meant for research wallets and is enabled by
+//! the `linkability` feature.
+//!
+//! Two of the metrics are the unnecessary input heuristics: if the smallest output is
+//! smaller than every input, it is likely change (otherwise an input would have been
+//! unnecessary), and if the inputs without the smallest one still cover the largest
+//! output, the spend does not look like a minimal payment. The third counts, for each
+//! output, the sets of inputs that could have funded it alone; the more there are, the
+//! weaker the link between the output and any input.
+
+use bitcoin::{Amount, TxOut};
+
+use crate::WeightedUtxo;
+
+/// The most inputs for which funding subsets are counted.
+pub const MAX_SUBSET_INPUTS: usize = 16;
+
+/// Linkability metrics of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Linkability {
+    /// Index of the output identified as change because it is the only one smaller than
+    /// every input.
+    pub identified_change: Option<usize>,
+    /// Whether the inputs without the smallest one cover the largest output.
+    pub unnecessary_input: bool,
+    /// For each output, the number of non-empty sets of inputs whose values cover it.
+    /// `None` with more than [`MAX_SUBSET_INPUTS`] inputs.
+    pub funding_subsets: Option<Vec<u64>>,
+}
+
+/// Analyzes the transaction spending `selection` to `outputs`.
+///
+/// Returns `None` if `selection` or `outputs` is empty, or if the input values overflow.
+pub fn analyze_linkability<'a, U, I>(selection: I, outputs: &[TxOut]) -> Option<Linkability>
+where
+    U: WeightedUtxo + 'a,
+    I: IntoIterator<Item = &'a U>,
+{
+    let inputs: Vec<Amount> = selection.into_iter().map(|utxo| utxo.value()).collect();
+    let min_input = *inputs.iter().min()?;
+    let max_output = outputs.iter().map(|output| output.value).max()?;
+
+    let mut below_inputs = outputs
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| output.value < min_input)
+        .map(|(index, _)| index);
+    let identified_change = match (below_inputs.next(), below_inputs.next()) {
+        (Some(index), None) => Some(index),
+        _ => None,
+    };
+
+    let total = inputs
+        .iter()
+        .try_fold(Amount::ZERO, |sum, &value| sum.checked_add(value))?;
+    let unnecessary_input = inputs.len() > 1 && total - min_input >= max_output;
+
+    let funding_subsets = if inputs.len() <= MAX_SUBSET_INPUTS {
+        let sums = subset_sums(&inputs);
+        Some(
+            outputs
+                .iter()
+                .map(|output| sums.iter().filter(|&&sum| sum >= output.value).count() as u64)
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    Some(Linkability {
+        identified_change,
+        unnecessary_input,
+        funding_subsets,
+    })
+}
+
+/// The sums of all non-empty subsets of `inputs`, whose total must not overflow.
+fn subset_sums(inputs: &[Amount]) -> Vec<Amount> {
+    let mut sums = vec![Amount::ZERO];
+    for &value in inputs {
+        let with: Vec<Amount> = sums.iter().map(|&sum| sum + value).collect();
+        sums.extend(with);
+    }
+    sums.remove(0);
+    sums
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::ScriptBuf;
+
+    use super::*;
+    use crate::tests::build_pool;
+
+    fn outputs(values: &[u64]) -> Vec<TxOut> {
+        values
+            .iter()
+            .map(|&value| TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn minimal_payment() {
+        let pool = build_pool(&[(6_000, 0), (5_000, 0)]);
+        let linkability = analyze_linkability(&pool, &outputs(&[8_000, 2_500])).unwrap();
+        assert_eq!(
+            linkability,
+            Linkability {
+                identified_change: Some(1),
+                unnecessary_input: false,
+                // {6000, 5000} covers 8 000; every non-empty set covers 2 500.
+                funding_subsets: Some(vec![1, 3]),
+            }
+        );
+    }
+
+    #[test]
+    fn unnecessary_input() {
+        let pool = build_pool(&[(6_000, 0), (5_000, 0)]);
+        let linkability = analyze_linkability(&pool, &outputs(&[4_000, 5_500])).unwrap();
+        assert_eq!(linkability.identified_change, Some(0));
+        assert!(linkability.unnecessary_input);
+        assert_eq!(linkability.funding_subsets, Some(vec![3, 2]));
+    }
+
+    #[test]
+    fn ambiguous_change() {
+        let pool = build_pool(&[(10_000, 0)]);
+        let linkability = analyze_linkability(&pool, &outputs(&[4_000, 5_000])).unwrap();
+        assert_eq!(linkability.identified_change, None);
+        assert!(!linkability.unnecessary_input);
+    }
+
+    #[test]
+    fn subsets_not_counted_for_large_selections() {
+        let pool = build_pool(&[(1_000, 0); MAX_SUBSET_INPUTS + 1]);
+        let linkability = analyze_linkability(&pool, &outputs(&[500])).unwrap();
+        assert_eq!(linkability.funding_subsets, None);
+    }
+
+    #[test]
+    fn empty_transaction() {
+        let pool = build_pool(&[(1_000, 0)]);
+        assert_eq!(analyze_linkability(&pool, &[]), None);
+        assert_eq!(analyze_linkability(&pool[..0], &outputs(&[500])), None);
+    }
+}
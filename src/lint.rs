@@ -0,0 +1,324 @@
+//! Pre-flight checks for candidate pools.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bitcoin::{Amount, FeeRate, SignedAmount, Weight};
+
+use crate::{defaults, WeightedUtxo};
+
+/// How candidates with a satisfaction weight of zero are treated.
+///
+/// A zero satisfaction weight is valid, but it is rarely what the caller meant: such a
+/// candidate only pays for its outpoint and sequence, so it is nearly free to spend and
+/// dominates every selection. The default is to allow them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ZeroWeightPolicy {
+    /// Accept zero weight candidates and report their indices.
+    #[default]
+    Allow,
+    /// Reject the pool if any candidate has a zero weight.
+    Reject,
+}
+
+impl ZeroWeightPolicy {
+    /// Applies the policy to `pool`.
+    ///
+    /// Returns the indices of all zero weight candidates under [`ZeroWeightPolicy::Allow`]
+    /// and an error naming the first one under [`ZeroWeightPolicy::Reject`].
+    pub fn check<U: WeightedUtxo>(&self, pool: &[U]) -> Result<Vec<usize>, ZeroWeightError> {
+        let flagged = zero_weight_candidates(pool);
+        match (self, flagged.first()) {
+            (ZeroWeightPolicy::Reject, Some(&index)) => Err(ZeroWeightError { index }),
+            _ => Ok(flagged),
+        }
+    }
+}
+
+/// A candidate with a satisfaction weight of zero was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZeroWeightError {
+    /// Index of the offending candidate in the pool.
+    pub index: usize,
+}
+
+impl fmt::Display for ZeroWeightError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "candidate {} has a satisfaction weight of zero",
+            self.index
+        )
+    }
+}
+
+impl std::error::Error for ZeroWeightError {}
+
+/// Returns the indices of the candidates in `pool` whose satisfaction weight is zero.
+pub fn zero_weight_candidates<U: WeightedUtxo>(pool: &[U]) -> Vec<usize> {
+    pool.iter()
+        .enumerate()
+        .filter(|(_, utxo)| utxo.satisfaction_weight() == Weight::ZERO)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// A suspicious candidate, or group of candidates, found by [`lint_pool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// The satisfaction weight is zero, see [`ZeroWeightPolicy`].
+    ZeroWeight {
+        /// Index of the candidate.
+        index: usize,
+    },
+    /// Spending the candidate alone exceeds the maximum standard transaction weight.
+    ExcessiveWeight {
+        /// Index of the candidate.
+        index: usize,
+        /// Weight of the input, or `None` if it overflows.
+        weight: Option<Weight>,
+    },
+    /// The value is below the dust limit and does not cover the fee for spending it.
+    HeavyDust {
+        /// Index of the candidate.
+        index: usize,
+        /// Effective value at the linted fee rate, or `None` if it overflows.
+        effective_value: Option<SignedAmount>,
+    },
+    /// More candidates than the configured threshold share the same value and weight,
+    /// wherever they are in the pool.
+    Duplicates {
+        /// Index of the first of the candidates.
+        first_index: usize,
+        /// Number of candidates sharing the value and weight.
+        count: usize,
+        /// The shared value.
+        value: Amount,
+        /// The shared satisfaction weight.
+        satisfaction_weight: Weight,
+    },
+}
+
+impl Lint {
+    /// The index of the candidate, or of the first of the duplicates.
+    fn index(&self) -> usize {
+        match *self {
+            Lint::ZeroWeight { index }
+            | Lint::ExcessiveWeight { index, .. }
+            | Lint::HeavyDust { index, .. } => index,
+            Lint::Duplicates { first_index, .. } => first_index,
+        }
+    }
+}
+
+/// Thresholds used by [`lint_pool_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LintConfig {
+    /// Inputs heavier than this are flagged. Defaults to 400 000 wu, the maximum
+    /// standard transaction weight.
+    pub max_weight: Weight,
+    /// Candidates below this value are considered dust. Defaults to 546 sats.
+    pub dust_limit: Amount,
+    /// More candidates than this with the same value and weight are flagged. Defaults to
+    /// 100.
+    pub max_duplicates: usize,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            max_weight: defaults::MAX_STANDARD_TX_WEIGHT,
+            dust_limit: defaults::DUST_LIMIT,
+            max_duplicates: defaults::MAX_DUPLICATES,
+        }
+    }
+}
+
+/// Checks `pool` for suspicious candidates using the default [`LintConfig`].
+///
+/// This is meant as a pre-flight check when ingesting UTXO data from external systems.
+/// Nothing is filtered; the returned warnings are sorted by candidate index.
+pub fn lint_pool<U: WeightedUtxo>(pool: &[U], fee_rate: FeeRate) -> Vec<Lint> {
+    lint_pool_with(pool, fee_rate, &LintConfig::default())
+}
+
+/// Checks `pool` for suspicious candidates using the thresholds in `config`.
+///
+/// Like [`lint_pool`], the returned warnings are sorted by candidate index.
+pub fn lint_pool_with<U: WeightedUtxo>(
+    pool: &[U],
+    fee_rate: FeeRate,
+    config: &LintConfig,
+) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut duplicates: BTreeMap<(Amount, Weight), (usize, usize)> = BTreeMap::new();
+
+    for (index, utxo) in pool.iter().enumerate() {
+        let satisfaction_weight = utxo.satisfaction_weight();
+        if satisfaction_weight == Weight::ZERO {
+            lints.push(Lint::ZeroWeight { index });
+        }
+
+        let weight = utxo.weight();
+        if weight.map_or(true, |w| w > config.max_weight) {
+            lints.push(Lint::ExcessiveWeight { index, weight });
+        }
+
+        let effective_value = utxo.effective_value(fee_rate);
+        if utxo.value() < config.dust_limit && effective_value.map_or(true, |v| !v.is_positive()) {
+            lints.push(Lint::HeavyDust {
+                index,
+                effective_value,
+            });
+        }
+
+        duplicates
+            .entry((utxo.value(), satisfaction_weight))
+            .or_insert((index, 0))
+            .1 += 1;
+    }
+
+    lints.extend(
+        duplicates
+            .into_iter()
+            .filter(|(_, (_, count))| *count > config.max_duplicates)
+            .map(
+                |((value, satisfaction_weight), (first_index, count))| Lint::Duplicates {
+                    first_index,
+                    count,
+                    value,
+                    satisfaction_weight,
+                },
+            ),
+    );
+    // Stable, so the warnings about one candidate keep their order.
+    lints.sort_by_key(Lint::index);
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_pool;
+
+    const FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_u32(10);
+
+    #[test]
+    fn allow_reports_zero_weight_candidates() {
+        let pool = build_pool(&[(1_000, 272), (2_000, 0), (3_000, 272), (4_000, 0)]);
+        assert_eq!(ZeroWeightPolicy::Allow.check(&pool), Ok(vec![1, 3]));
+    }
+
+    #[test]
+    fn reject_names_first_zero_weight_candidate() {
+        let pool = build_pool(&[(1_000, 272), (2_000, 0), (3_000, 0)]);
+        assert_eq!(
+            ZeroWeightPolicy::Reject.check(&pool),
+            Err(ZeroWeightError { index: 1 })
+        );
+    }
+
+    #[test]
+    fn reject_accepts_weighted_pool() {
+        let pool = build_pool(&[(1_000, 272), (2_000, 272)]);
+        assert_eq!(ZeroWeightPolicy::Reject.check(&pool), Ok(vec![]));
+    }
+
+    #[test]
+    fn zero_weight_still_pays_for_base_weight() {
+        let utxo = &build_pool(&[(1_000, 0)])[0];
+        let fee_rate = bitcoin::FeeRate::from_sat_per_vb_u32(1);
+        assert_eq!(
+            utxo.effective_value(fee_rate),
+            Some(bitcoin::SignedAmount::from_sat(960))
+        );
+    }
+
+    #[test]
+    fn lint_clean_pool() {
+        let pool = build_pool(&[(10_000, 272), (20_000, 272), (546, 108)]);
+        assert_eq!(lint_pool(&pool, FeeRate::from_sat_per_vb_u32(1)), vec![]);
+    }
+
+    #[test]
+    fn lint_zero_weight() {
+        let pool = build_pool(&[(10_000, 272), (20_000, 272), (546, 0)]);
+        assert_eq!(
+            lint_pool(&pool, FeeRate::from_sat_per_vb_u32(1)),
+            vec![Lint::ZeroWeight { index: 2 }]
+        );
+    }
+
+    #[test]
+    fn lint_excessive_weight() {
+        let pool = build_pool(&[(10_000, 272), (10_000_000, 400_000), (1, u64::MAX)]);
+        let lints = lint_pool(&pool, FeeRate::ZERO);
+        assert_eq!(
+            lints,
+            vec![
+                Lint::ExcessiveWeight {
+                    index: 1,
+                    weight: Some(Weight::from_wu(400_160))
+                },
+                Lint::ExcessiveWeight {
+                    index: 2,
+                    weight: None
+                },
+                Lint::HeavyDust {
+                    index: 2,
+                    effective_value: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_heavy_dust() {
+        // A P2PKH input costs 1480 sats to spend at 10 sat/vB, but only values below the
+        // dust limit are flagged.
+        let pool = build_pool(&[(545, 432), (546, 432), (2_000, 432)]);
+        assert_eq!(
+            lint_pool(&pool, FEE_RATE),
+            vec![Lint::HeavyDust {
+                index: 0,
+                effective_value: Some(SignedAmount::from_sat(-935))
+            }]
+        );
+    }
+
+    #[test]
+    fn lint_duplicates() {
+        let config = LintConfig {
+            max_duplicates: 2,
+            ..Default::default()
+        };
+        let pool = build_pool(&[
+            (5_000, 272),
+            (2_000, 0),
+            (1_000, 272),
+            (1_000, 272),
+            (5_000, 272),
+            (1_000, 272),
+            (5_000, 272),
+            (5_000, 300),
+        ]);
+        assert_eq!(
+            lint_pool_with(&pool, FEE_RATE, &config),
+            vec![
+                Lint::Duplicates {
+                    first_index: 0,
+                    count: 3,
+                    value: Amount::from_sat(5_000),
+                    satisfaction_weight: Weight::from_wu(272),
+                },
+                Lint::ZeroWeight { index: 1 },
+                Lint::Duplicates {
+                    first_index: 2,
+                    count: 3,
+                    value: Amount::from_sat(1_000),
+                    satisfaction_weight: Weight::from_wu(272),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Filtering candidates by confirmations.
+//!
+//! Coinbase outputs can't be spent until they are buried deep enough, and many wallets
+//! only spend coins with a few confirmations. A [`ConfirmationPolicy`] applied to a pool
+//! freezes the candidates it doesn't allow, so they are rejected like any frozen
+//! candidate while their indices stay the same.
+
+use bitcoin::{Amount, OutPoint, Script, SignedAmount, Weight};
+
+use crate::{UtxoChainInfo, UtxoMetadata, WeightedUtxo};
+
+/// Confirmations a coinbase output needs before a transaction spending it can be mined,
+/// as set by consensus.
+pub const COINBASE_MATURITY: u32 = 100;
+
+/// Which candidates may be spent, judged by their chain state.
+///
+/// Immature coinbase outputs are never allowed. The default policy allows everything
+/// else, including unconfirmed outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConfirmationPolicy {
+    /// The fewest confirmations a candidate needs.
+    pub min_confirmations: u32,
+}
+
+impl ConfirmationPolicy {
+    /// Creates a policy requiring `min_confirmations`.
+    pub const fn new(min_confirmations: u32) -> Self {
+        ConfirmationPolicy { min_confirmations }
+    }
+
+    /// Returns `true` if the policy allows spending `utxo`.
+    pub fn allows<U: UtxoChainInfo + ?Sized>(&self, utxo: &U) -> bool {
+        utxo.is_mature() && utxo.confirmations() >= self.min_confirmations
+    }
+
+    /// Applies the policy to `pool`, freezing the candidates it doesn't allow.
+    ///
+    /// The result has a candidate for each of `pool` at the same index and can be passed
+    /// to any selector.
+    pub fn apply<'a, U: UtxoChainInfo>(&self, pool: &'a [U]) -> Vec<Filtered<'a, U>> {
+        pool.iter()
+            .map(|utxo| Filtered {
+                utxo,
+                allowed: self.allows(utxo),
+            })
+            .collect()
+    }
+}
+
+/// A candidate with the verdict of a [`ConfirmationPolicy`].
+///
+/// It behaves like the candidate, except that it is frozen if the policy doesn't allow
+/// it, see [`WeightedUtxo::is_spendable`].
+#[derive(Debug)]
+pub struct Filtered<'a, U> {
+    utxo: &'a U,
+    allowed: bool,
+}
+
+impl<U> Clone for Filtered<'_, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<U> Copy for Filtered<'_, U> {}
+
+impl<'a, U> Filtered<'a, U> {
+    /// The candidate.
+    pub fn utxo(&self) -> &'a U {
+        self.utxo
+    }
+
+    /// Returns `true` if the policy allows spending the candidate.
+    pub fn is_allowed(&self) -> bool {
+        self.allowed
+    }
+}
+
+impl<U: WeightedUtxo> WeightedUtxo for Filtered<'_, U> {
+    fn satisfaction_weight(&self) -> Weight {
+        self.utxo.satisfaction_weight()
+    }
+
+    fn value(&self) -> Amount {
+        self.utxo.value()
+    }
+
+    fn weight_margin(&self) -> Weight {
+        self.utxo.weight_margin()
+    }
+
+    fn fee_adjustment(&self) -> SignedAmount {
+        self.utxo.fee_adjustment()
+    }
+
+    fn ancestor_fee(&self) -> Amount {
+        self.utxo.ancestor_fee()
+    }
+
+    fn ancestor_weight(&self) -> Weight {
+        self.utxo.ancestor_weight()
+    }
+
+    fn is_spendable(&self) -> bool {
+        self.allowed && self.utxo.is_spendable()
+    }
+}
+
+impl<U: UtxoMetadata> UtxoMetadata for Filtered<'_, U> {
+    fn outpoint(&self) -> OutPoint {
+        self.utxo.outpoint()
+    }
+
+    fn script_pubkey(&self) -> &Script {
+        self.utxo.script_pubkey()
+    }
+}
+
+impl<U: UtxoChainInfo> UtxoChainInfo for Filtered<'_, U> {
+    fn confirmations(&self) -> u32 {
+        self.utxo.confirmations()
+    }
+
+    fn is_coinbase(&self) -> bool {
+        self.utxo.is_coinbase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::FeeRate;
+
+    use super::*;
+    use crate::candidate::eligible_candidates;
+
+    struct Coin {
+        confirmations: u32,
+        coinbase: bool,
+    }
+
+    impl WeightedUtxo for Coin {
+        fn satisfaction_weight(&self) -> Weight {
+            Weight::from_wu(272)
+        }
+
+        fn value(&self) -> Amount {
+            Amount::from_sat(10_000)
+        }
+    }
+
+    impl UtxoChainInfo for Coin {
+        fn confirmations(&self) -> u32 {
+            self.confirmations
+        }
+
+        fn is_coinbase(&self) -> bool {
+            self.coinbase
+        }
+    }
+
+    fn coin(confirmations: u32, coinbase: bool) -> Coin {
+        Coin {
+            confirmations,
+            coinbase,
+        }
+    }
+
+    #[test]
+    fn coinbase_maturity() {
+        assert!(coin(0, false).is_mature());
+        assert!(!coin(99, true).is_mature());
+        assert!(coin(100, true).is_mature());
+    }
+
+    #[test]
+    fn policy_freezes_disallowed_candidates() {
+        let pool = [
+            coin(0, false),
+            coin(6, false),
+            coin(50, true),
+            coin(120, true),
+        ];
+
+        let allowed: Vec<bool> = ConfirmationPolicy::default()
+            .apply(&pool)
+            .iter()
+            .map(Filtered::is_allowed)
+            .collect();
+        assert_eq!(allowed, vec![true, true, false, true]);
+
+        let filtered = ConfirmationPolicy::new(1).apply(&pool);
+        let indices: Vec<usize> = eligible_candidates(&filtered, FeeRate::ZERO, |_| {})
+            .iter()
+            .map(|c| c.index)
+            .collect();
+        assert_eq!(indices, vec![1, 3]);
+    }
+}
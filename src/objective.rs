@@ -0,0 +1,117 @@
+//! Ranking alternative selections.
+//!
+//! Selectors return one selection each, and a wallet running several of them has to pick
+//! one. Bitcoin Core keeps the one with the least waste. A hot wallet may rather limit
+//! how much it exposes in a single signing operation: every input signed is at risk if the
+//! signing host is compromised, so it keeps the selection spending the least above the
+//! target, even if that wastes more in fees.
+
+use bitcoin::{Amount, FeeRate, SignedAmount};
+
+use crate::{SelectionReport, WeightedUtxo};
+
+/// What to minimize when choosing between selections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Objective {
+    /// The waste, see [`SelectionReport::waste`].
+    MinimizeWaste {
+        /// The fee rate at which the inputs would be spent later.
+        long_term_fee_rate: FeeRate,
+        /// The cost of creating and later spending a change output.
+        change_cost: Amount,
+        /// Excesses of at least this go to a change output, smaller ones to fees.
+        min_change: Amount,
+    },
+    /// The input value exceeding the target, the change plus the fee for the inputs.
+    ///
+    /// [Fee adjustments](crate::WeightedUtxo::fee_adjustment) are ignored, since the
+    /// whole input value is exposed.
+    MinimizeValueAtRisk,
+}
+
+impl Objective {
+    /// Returns the cost of `report` under this objective, lower is better.
+    ///
+    /// Returns `None` on overflow.
+    pub fn cost<U: WeightedUtxo>(&self, report: &SelectionReport<'_, U>) -> Option<SignedAmount> {
+        match *self {
+            Objective::MinimizeWaste {
+                long_term_fee_rate,
+                change_cost,
+                min_change,
+            } => {
+                let change_cost = (report.excess >= min_change).then_some(change_cost);
+                report.waste(long_term_fee_rate, change_cost)
+            }
+            Objective::MinimizeValueAtRisk => {
+                let target = report.effective_value.checked_sub(report.excess)?;
+                report
+                    .input_value
+                    .to_signed()
+                    .ok()?
+                    .checked_sub(target.to_signed().ok()?)
+            }
+        }
+    }
+
+    /// Returns the report of `reports` with the lowest cost.
+    ///
+    /// Ties go to the first report. Reports whose cost overflows are skipped.
+    pub fn best<'r, 'a: 'r, U, I>(&self, reports: I) -> Option<&'r SelectionReport<'a, U>>
+    where
+        U: WeightedUtxo + 'a,
+        I: IntoIterator<Item = &'r SelectionReport<'a, U>>,
+    {
+        let mut best: Option<(SignedAmount, &SelectionReport<'a, U>)> = None;
+        for report in reports {
+            let Some(cost) = self.cost(report) else {
+                continue;
+            };
+            if best.map_or(true, |(best, _)| cost < best) {
+                best = Some((cost, report));
+            }
+        }
+        best.map(|(_, report)| report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::candidate::eligible_candidates;
+    use crate::tests::build_pool;
+
+    #[test]
+    fn value_at_risk_differs_from_waste() {
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        let pool = build_pool(&[(100_000, 272), (6_000, 272), (5_900, 272)]);
+        let candidates = eligible_candidates(&pool, fee_rate, |_| {});
+        let target = Amount::from_sat(9_000);
+
+        // The large coin leaves change, the two small ones leave 740 to fees.
+        let large = SelectionReport::new(vec![candidates[0]], target, 0).unwrap();
+        let small = SelectionReport::new(candidates[1..].to_vec(), target, 0).unwrap();
+        let reports = [large, small];
+
+        let waste = Objective::MinimizeWaste {
+            long_term_fee_rate: FeeRate::from_sat_per_vb_u32(5),
+            change_cost: Amount::from_sat(1_000),
+            min_change: Amount::from_sat(5_000),
+        };
+        assert_eq!(waste.cost(&reports[0]), Some(SignedAmount::from_sat(1_540)));
+        assert_eq!(waste.cost(&reports[1]), Some(SignedAmount::from_sat(1_820)));
+        assert_eq!(waste.best(&reports).map(|r| r.selection[0].index), Some(0));
+
+        let risk = Objective::MinimizeValueAtRisk;
+        assert_eq!(risk.cost(&reports[0]), Some(SignedAmount::from_sat(91_000)));
+        assert_eq!(risk.cost(&reports[1]), Some(SignedAmount::from_sat(2_900)));
+        assert_eq!(risk.best(&reports).map(|r| r.selection[0].index), Some(1));
+    }
+
+    #[test]
+    fn best_of_nothing() {
+        let reports: Vec<SelectionReport<'_, crate::tests::Utxo>> = Vec::new();
+        assert!(Objective::MinimizeValueAtRisk.best(&reports).is_none());
+    }
+}
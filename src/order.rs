@@ -0,0 +1,221 @@
+//! Canonical ordering of selected candidates.
+//!
+//! Algorithms that sort the pool internally return their selection in an order that
+//! depends on how ties were broken, so two runs over the same pool can list the same
+//! inputs differently. Every selection returned by this crate is put into the canonical
+//! order defined here, so equal selections always compare equal.
+//!
+//! Transaction builders may want the inputs in another order. [`InputOrder`] names the
+//! ones [`SelectionReport::inputs`] can produce, keeping each input's pool index, and
+//! [`SelectionReport::inputs_bip69`] sorts them as BIP 69 does. To reorder data kept
+//! alongside the inputs, such as signatures, [`bip69_permutation`] and
+//! [`random_permutation`] return the order as a permutation instead.
+//!
+//! [`SelectionReport::inputs`]: crate::SelectionReport::inputs
+//! [`SelectionReport::inputs_bip69`]: crate::SelectionReport::inputs_bip69
+
+use core::cmp::Reverse;
+
+use bitcoin::hashes::Hash;
+use bitcoin::{FeeRate, OutPoint};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::candidate::Candidate;
+use crate::{UtxoMetadata, WeightedUtxo};
+
+/// An order of the inputs of a selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum InputOrder {
+    /// The canonical order of [`sort_candidates`], as the selection is reported.
+    #[default]
+    Canonical,
+    /// By descending value, then by ascending pool index.
+    Value,
+    /// By ascending weight, then by ascending pool index.
+    Weight,
+    /// By ascending pool index, the order the candidates were given in.
+    Pool,
+}
+
+/// Sorts `selection` into canonical order.
+///
+/// Each entry is a candidate with its index in the caller's pool. Candidates are sorted by
+/// descending effective value at `fee_rate`, then by ascending weight and finally by
+/// ascending pool index. Candidates whose effective value or weight overflows sort last.
+pub fn sort_canonical<U: WeightedUtxo>(selection: &mut [(usize, &U)], fee_rate: FeeRate) {
+    selection.sort_by_cached_key(|&(index, utxo)| {
+        let effective_value = utxo.effective_value(fee_rate);
+        let weight = utxo.weight();
+        (
+            effective_value.is_none(),
+            Reverse(effective_value),
+            weight.is_none(),
+            weight,
+            index,
+        )
+    });
+}
+
+/// Sorts prepared candidates into canonical order.
+///
+/// This is the order of [`sort_canonical`], using the values computed during preparation.
+pub fn sort_candidates<U>(candidates: &mut [Candidate<'_, U>]) {
+    candidates.sort_unstable_by_key(|c| (Reverse(c.effective_value), c.weight, c.index));
+}
+
+/// Sorts `candidates` into `order`.
+pub(crate) fn sort_inputs<U: WeightedUtxo>(candidates: &mut [Candidate<'_, U>], order: InputOrder) {
+    match order {
+        InputOrder::Canonical => sort_candidates(candidates),
+        InputOrder::Value => {
+            candidates.sort_unstable_by_key(|c| (Reverse(c.utxo.value()), c.index))
+        }
+        InputOrder::Weight => candidates.sort_unstable_by_key(|c| (c.weight, c.index)),
+        InputOrder::Pool => candidates.sort_unstable_by_key(|c| c.index),
+    }
+}
+
+/// Sorts `candidates` as BIP 69 sorts inputs.
+///
+/// Inputs are sorted by the previous transaction id, compared as displayed, then by the
+/// output index.
+pub(crate) fn sort_bip69<U: UtxoMetadata>(candidates: &mut [Candidate<'_, U>]) {
+    candidates.sort_by_cached_key(|c| bip69_key(c.utxo.outpoint()));
+}
+
+/// The permutation sorting `inputs` as BIP 69 does.
+///
+/// The `i`th input in BIP 69 order is `inputs[permutation[i]]`, so anything indexed like
+/// `inputs` can be reordered the same way. Inputs spending the same outpoint keep their
+/// relative order.
+pub fn bip69_permutation<U: UtxoMetadata>(inputs: &[U]) -> Vec<usize> {
+    let mut permutation: Vec<usize> = (0..inputs.len()).collect();
+    permutation.sort_by_cached_key(|&i| bip69_key(inputs[i].outpoint()));
+    permutation
+}
+
+/// A uniformly random permutation of `len` inputs drawn from `rng`.
+///
+/// Ordering inputs randomly reveals nothing about the wallet, unlike an order that
+/// depends on how they were selected. The permutation is applied like the one of
+/// [`bip69_permutation`].
+pub fn random_permutation<R: Rng + ?Sized>(len: usize, rng: &mut R) -> Vec<usize> {
+    let mut permutation: Vec<usize> = (0..len).collect();
+    permutation.shuffle(rng);
+    permutation
+}
+
+/// The key BIP 69 sorts inputs by: the transaction id, compared as displayed, and then
+/// the output index.
+fn bip69_key(outpoint: OutPoint) -> ([u8; 32], u32) {
+    let mut txid = outpoint.txid.to_byte_array();
+    txid.reverse();
+    (txid, outpoint.vout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_pool;
+
+    fn indices<U>(selection: &[(usize, &U)]) -> Vec<usize> {
+        selection.iter().map(|(i, _)| *i).collect()
+    }
+
+    #[test]
+    fn sort_by_effective_value_then_weight_then_index() {
+        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+        let pool = build_pool(&[
+            (1_000, 272), // 1000 - 108 = 892
+            (5_000, 272), // 4892
+            (1_000, 272), // 892
+            (1_004, 288), // 1004 - 112 = 892, heavier
+            (u64::MAX, 0),
+        ]);
+
+        let mut selection: Vec<_> = pool.iter().enumerate().rev().collect();
+        sort_canonical(&mut selection, fee_rate);
+        assert_eq!(indices(&selection), vec![1, 0, 2, 3, 4]);
+    }
+
+    #[test]
+    fn sort_is_independent_of_input_order() {
+        let fee_rate = FeeRate::from_sat_per_vb_u32(5);
+        let pool = build_pool(&[(3_000, 272), (3_000, 272), (2_000, 100), (9_000, 500)]);
+
+        let mut forward: Vec<_> = pool.iter().enumerate().collect();
+        let mut backward: Vec<_> = pool.iter().enumerate().rev().collect();
+        sort_canonical(&mut forward, fee_rate);
+        sort_canonical(&mut backward, fee_rate);
+
+        assert_eq!(indices(&forward), indices(&backward));
+    }
+
+    #[test]
+    fn input_orders() {
+        use bitcoin::{Amount, ScriptBuf, TxOut, Txid, Weight};
+
+        use crate::prefix_sum::select_coins_prefix_sum;
+        use crate::utxo::Utxo;
+
+        // Displayed, `low` sorts first, while its bytes sort last.
+        let mut bytes = [0; 32];
+        bytes[0] = 1;
+        let low = Txid::from_byte_array(bytes);
+        bytes.reverse();
+        let high = Txid::from_byte_array(bytes);
+
+        let utxo = |txid, vout, value, weight| {
+            let txout = TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new(),
+            };
+            Utxo::new(OutPoint { txid, vout }, txout, Weight::from_wu(weight))
+        };
+        let pool = vec![
+            utxo(high, 0, 5_000, 272),
+            utxo(low, 1, 5_000, 100),
+            utxo(low, 0, 9_000, 500),
+        ];
+        let report =
+            select_coins_prefix_sum(Amount::from_sat(19_000), FeeRate::ZERO, &pool).unwrap();
+
+        let inputs = |order| indices(&report.inputs(order));
+        assert_eq!(inputs(InputOrder::Canonical), vec![2, 1, 0]);
+        assert_eq!(inputs(InputOrder::Value), vec![2, 0, 1]);
+        assert_eq!(inputs(InputOrder::Weight), vec![1, 0, 2]);
+        assert_eq!(inputs(InputOrder::Pool), vec![0, 1, 2]);
+        assert_eq!(indices(&report.inputs_bip69()), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn permutations() {
+        use bitcoin::{Amount, ScriptBuf, TxOut, Txid, Weight};
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        use crate::utxo::Utxo;
+
+        let mut bytes = [0; 32];
+        bytes[0] = 1;
+        let low = Txid::from_byte_array(bytes);
+        bytes.reverse();
+        let high = Txid::from_byte_array(bytes);
+
+        let utxo = |txid, vout| {
+            let txout = TxOut {
+                value: Amount::from_sat(1_000),
+                script_pubkey: ScriptBuf::new(),
+            };
+            Utxo::new(OutPoint { txid, vout }, txout, Weight::ZERO)
+        };
+        let inputs = vec![utxo(high, 0), utxo(low, 2), utxo(low, 1)];
+        assert_eq!(bip69_permutation(&inputs), vec![2, 1, 0]);
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut permutation = random_permutation(10, &mut rng);
+        permutation.sort_unstable();
+        assert_eq!(permutation, (0..10).collect::<Vec<_>>());
+    }
+}
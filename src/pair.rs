@@ -0,0 +1,172 @@
+//! Selecting for two transactions at once.
+//!
+//! Some protocols build two transactions together, for example a payment and a
+//! pre-signed child to bump its fee. Both must be funded from the same pool without
+//! sharing a coin. Selecting for one after the other can leave too little for the second,
+//! or make it expensive, so [`select_pair`] tries both orders and keeps the cheaper
+//! result.
+
+use bitcoin::{Amount, FeeRate};
+
+use crate::candidate::Candidate;
+use crate::order::sort_candidates;
+use crate::prefix_sum::select_coins_prefix_sum;
+use crate::{SelectionError, SelectionReport, WeightedUtxo};
+
+/// What one transaction of a pair needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PairTarget {
+    /// The amount the effective values of the selection must cover.
+    pub target: Amount,
+    /// The fee rate of the transaction.
+    pub fee_rate: FeeRate,
+}
+
+/// Selects disjoint sets of candidates from `pool` for `first` and `second`.
+///
+/// Each set is selected with [`select_coins_prefix_sum`], once selecting for `first`
+/// first and once for `second` first, from what the other one left. Of the orders that
+/// succeed, the one paying the lower total fee for the inputs is kept, preferring the
+/// given order on ties.
+///
+/// Returns the reports for `first` and `second`, with indices into `pool`. If neither
+/// order succeeds, the error of selecting in the given order is returned.
+pub fn select_pair<U: WeightedUtxo>(
+    first: PairTarget,
+    second: PairTarget,
+    pool: &[U],
+) -> Result<(SelectionReport<'_, U>, SelectionReport<'_, U>), SelectionError> {
+    let given = select_in_order(first, second, pool);
+    let reversed = select_in_order(second, first, pool).map(|(second, first)| (first, second));
+
+    match (given, reversed) {
+        (Ok(given), Ok(reversed)) => {
+            let total_fee = |(first, second): &(SelectionReport<'_, U>, SelectionReport<'_, U>)| {
+                first.fee.checked_add(second.fee)
+            };
+            if total_fee(&reversed) < total_fee(&given) {
+                Ok(reversed)
+            } else {
+                Ok(given)
+            }
+        }
+        (Err(_), Ok(reversed)) => Ok(reversed),
+        (given, _) => given,
+    }
+}
+
+/// Selects for `first` from `pool`, then for `second` from the rest.
+fn select_in_order<U: WeightedUtxo>(
+    first: PairTarget,
+    second: PairTarget,
+    pool: &[U],
+) -> Result<(SelectionReport<'_, U>, SelectionReport<'_, U>), SelectionError> {
+    let first = select_coins_prefix_sum(first.target, first.fee_rate, pool)?;
+
+    let rest: Vec<(usize, &U)> = pool
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| !first.selection.iter().any(|c| c.index == index))
+        .collect();
+    let rest_pool: Vec<&U> = rest.iter().map(|&(_, utxo)| utxo).collect();
+    let report = select_coins_prefix_sum(second.target, second.fee_rate, &rest_pool)?;
+
+    let mut selection: Vec<Candidate<'_, U>> = report
+        .selection
+        .iter()
+        .map(|c| {
+            let (index, utxo) = rest[c.index];
+            Candidate {
+                index,
+                utxo,
+                effective_value: c.effective_value,
+                weight: c.weight,
+            }
+        })
+        .collect();
+    sort_candidates(&mut selection);
+    let second = SelectionReport::new(selection, second.target, 0)?;
+    Ok((first, second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_pool;
+
+    fn target(sats: u64) -> PairTarget {
+        PairTarget {
+            target: Amount::from_sat(sats),
+            fee_rate: FeeRate::ZERO,
+        }
+    }
+
+    fn indices<U>(report: &SelectionReport<'_, U>) -> Vec<usize> {
+        report.selection.iter().map(|c| c.index).collect()
+    }
+
+    #[test]
+    fn disjoint_selections() {
+        let pool = build_pool(&[(100_000, 0), (60_000, 0), (40_000, 0)]);
+        let (first, second) = select_pair(target(50_000), target(95_000), &pool).unwrap();
+        assert_eq!(indices(&first), vec![1]);
+        assert_eq!(indices(&second), vec![0]);
+    }
+
+    #[test]
+    fn order_is_reversed_when_needed() {
+        // Selecting for the first target takes the only candidate covering the second.
+        let pool = build_pool(&[(100_000, 0), (60_000, 0), (30_000, 0)]);
+        assert!(select_in_order(target(70_000), target(95_000), &pool).is_err());
+
+        let (first, second) = select_pair(target(70_000), target(95_000), &pool).unwrap();
+        assert_eq!(indices(&first), vec![1, 2]);
+        assert_eq!(indices(&second), vec![0]);
+    }
+
+    #[test]
+    fn cheaper_order_is_kept() {
+        // Each input pays 108 sats at 1 sat/vB, leaving round effective values.
+        let pool = build_pool(&[
+            (50_108, 272),
+            (40_108, 272),
+            (40_108, 272),
+            (60_108, 272),
+            (20_108, 272),
+        ]);
+        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+        let first = PairTarget {
+            target: Amount::from_sat(140_000),
+            fee_rate,
+        };
+        let second = PairTarget {
+            target: Amount::from_sat(50_000),
+            fee_rate,
+        };
+
+        // In the given order the first target takes the 50 000 candidate and the second
+        // needs two inputs.
+        let (given_first, given_second) = select_in_order(first, second, &pool).unwrap();
+        assert_eq!(
+            given_first.fee + given_second.fee,
+            Amount::from_sat(5 * 108)
+        );
+
+        let (first, second) = select_pair(first, second, &pool).unwrap();
+        assert_eq!(indices(&first), vec![3, 1, 2]);
+        assert_eq!(indices(&second), vec![0]);
+        assert_eq!(first.fee + second.fee, Amount::from_sat(4 * 108));
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        let pool = build_pool(&[(100_000, 0), (60_000, 0)]);
+        assert_eq!(
+            select_pair(target(70_000), target(95_000), &pool).map(|_| ()),
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(60_000),
+                required: Amount::from_sat(95_000),
+            })
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Candidates tagged with the funding source they belong to.
+//!
+//! A treasury may fund a payment from several internal accounts. Tagging each candidate
+//! with a partition key allows reporting how much each account contributed to a selection
+//! and how much of the fee it should bear.
+
+use std::collections::BTreeMap;
+
+use bitcoin::Amount;
+
+use crate::WeightedUtxo;
+
+/// A candidate belonging to a partition, such as an account or a co-owner.
+pub trait Partitioned {
+    /// Identifies the partition.
+    type Key: Ord + Clone;
+
+    /// The partition this candidate belongs to.
+    fn partition(&self) -> Self::Key;
+}
+
+/// The share of a fee allocated to one partition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeAllocation<K> {
+    /// The partition.
+    pub partition: K,
+    /// Total value of the selected candidates of this partition.
+    pub contributed: Amount,
+    /// The part of the fee borne by this partition.
+    pub fee: Amount,
+}
+
+/// Splits `fee` across the partitions of `selection` proportionally to contributed value.
+///
+/// Shares are rounded down and the remaining satoshis are handed out one at a time to the
+/// partitions with the largest remainders, so the shares always sum to exactly `fee`. Ties
+/// are broken in key order. The result is sorted by partition key.
+///
+/// Returns `None` on overflow, or if a non-zero fee is to be split over a selection
+/// without value.
+pub fn allocate_fee<'a, U, I>(selection: I, fee: Amount) -> Option<Vec<FeeAllocation<U::Key>>>
+where
+    U: WeightedUtxo + Partitioned + 'a,
+    I: IntoIterator<Item = &'a U>,
+{
+    let mut contributions: BTreeMap<U::Key, Amount> = BTreeMap::new();
+    for utxo in selection {
+        let contributed = contributions
+            .entry(utxo.partition())
+            .or_insert(Amount::ZERO);
+        *contributed = contributed.checked_add(utxo.value())?;
+    }
+
+    let total = contributions
+        .values()
+        .try_fold(Amount::ZERO, |sum, v| sum.checked_add(*v))?;
+    if total == Amount::ZERO && fee > Amount::ZERO {
+        return None;
+    }
+
+    let fee_sat = u128::from(fee.to_sat());
+    let total_sat = u128::from(total.to_sat()).max(1);
+
+    let mut allocations = Vec::with_capacity(contributions.len());
+    let mut remainders = Vec::with_capacity(contributions.len());
+    let mut allocated = 0;
+    for (i, (partition, contributed)) in contributions.into_iter().enumerate() {
+        let scaled = fee_sat * u128::from(contributed.to_sat());
+        let share = (scaled / total_sat) as u64;
+        allocated += share;
+        remainders.push((scaled % total_sat, i));
+        allocations.push(FeeAllocation {
+            partition,
+            contributed,
+            fee: Amount::from_sat(share),
+        });
+    }
+
+    // Largest remainder first, earlier key first on ties.
+    remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    let leftover = (fee.to_sat() - allocated) as usize;
+    for &(_, i) in remainders.iter().take(leftover) {
+        allocations[i].fee += Amount::from_sat(1);
+    }
+
+    Some(allocations)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Weight;
+
+    use super::*;
+
+    struct AccountUtxo {
+        account: &'static str,
+        value: Amount,
+    }
+
+    impl WeightedUtxo for AccountUtxo {
+        fn satisfaction_weight(&self) -> Weight {
+            Weight::from_wu(272)
+        }
+        fn value(&self) -> Amount {
+            self.value
+        }
+    }
+
+    impl Partitioned for AccountUtxo {
+        type Key = &'static str;
+        fn partition(&self) -> Self::Key {
+            self.account
+        }
+    }
+
+    fn utxos(values: &[(&'static str, u64)]) -> Vec<AccountUtxo> {
+        values
+            .iter()
+            .map(|&(account, value)| AccountUtxo {
+                account,
+                value: Amount::from_sat(value),
+            })
+            .collect()
+    }
+
+    fn fees(allocations: &[FeeAllocation<&'static str>]) -> Vec<(&'static str, u64)> {
+        allocations
+            .iter()
+            .map(|a| (a.partition, a.fee.to_sat()))
+            .collect()
+    }
+
+    #[test]
+    fn allocate_proportionally() {
+        let selection = utxos(&[("ops", 30_000), ("savings", 10_000), ("ops", 60_000)]);
+        let allocations = allocate_fee(&selection, Amount::from_sat(1_000)).unwrap();
+
+        assert_eq!(fees(&allocations), vec![("ops", 900), ("savings", 100)]);
+        assert_eq!(allocations[0].contributed, Amount::from_sat(90_000));
+    }
+
+    #[test]
+    fn remainders_sum_to_fee() {
+        let selection = utxos(&[("a", 1), ("b", 1), ("c", 1)]);
+        let allocations = allocate_fee(&selection, Amount::from_sat(100)).unwrap();
+
+        assert_eq!(fees(&allocations), vec![("a", 34), ("b", 33), ("c", 33)]);
+    }
+
+    #[test]
+    fn largest_remainder_wins() {
+        let selection = utxos(&[("a", 1), ("b", 2)]);
+        let allocations = allocate_fee(&selection, Amount::from_sat(2)).unwrap();
+
+        // a: 2/3 = 0.67, b: 4/3 = 1.33
+        assert_eq!(fees(&allocations), vec![("a", 1), ("b", 1)]);
+    }
+
+    #[test]
+    fn zero_fee() {
+        let selection = utxos(&[("a", 0), ("b", 5)]);
+        let allocations = allocate_fee(&selection, Amount::ZERO).unwrap();
+
+        assert_eq!(fees(&allocations), vec![("a", 0), ("b", 0)]);
+    }
+
+    #[test]
+    fn fee_without_value() {
+        let selection = utxos(&[("a", 0)]);
+        assert_eq!(allocate_fee(&selection, Amount::from_sat(1)), None);
+        assert_eq!(allocate_fee(&utxos(&[]), Amount::from_sat(1)), None);
+    }
+
+    #[test]
+    fn large_values_do_not_overflow() {
+        let selection = utxos(&[("a", Amount::MAX_MONEY.to_sat()), ("b", 1)]);
+        let allocations = allocate_fee(&selection, Amount::MAX_MONEY).unwrap();
+
+        assert_eq!(allocations[0].fee + allocations[1].fee, Amount::MAX_MONEY);
+    }
+}
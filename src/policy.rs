@@ -0,0 +1,208 @@
+//! Fee rate dependent choice of a selection strategy.
+//!
+//! Which algorithm gives the best result depends mostly on the current fee rate. When
+//! fees are high every extra input is expensive and the selection should minimize weight.
+//! When fees are low it is cheap to spend many small coins now instead of later. In
+//! between, knapsack balances the two.
+//!
+//! When the caller knows how urgent the payment is rather than the fee rate,
+//! [`ConfTargetPolicy`] chooses the strategy from the confirmation target and resolves the
+//! fee rates through a [`FeeEstimator`]. It is passed to
+//! [`CoinSelector::conf_target`](crate::CoinSelector::conf_target).
+
+use bitcoin::FeeRate;
+
+use crate::defaults;
+use crate::estimator::FeeEstimator;
+
+/// Selection strategy chosen by an [`AutoPolicy`] or a [`ConfTargetPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Strategy {
+    /// Select the lowest weight input set (CoinGrinder).
+    MinimizeWeight,
+    /// Prefer spending many small inputs to shrink the pool.
+    Consolidate,
+    /// Knapsack, which the other strategies also fall back to when they find no selection
+    /// within the limits.
+    Standard,
+}
+
+/// Chooses a [`Strategy`] from the fee rate of the transaction being built.
+///
+/// The default thresholds use CoinGrinder above 30 sat/vB and consolidate below 3 sat/vB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AutoPolicy {
+    /// Fee rates strictly above this use [`Strategy::MinimizeWeight`].
+    pub minimize_weight_above: FeeRate,
+    /// Fee rates strictly below this use [`Strategy::Consolidate`].
+    pub consolidate_below: FeeRate,
+}
+
+impl AutoPolicy {
+    /// Creates a policy with the given thresholds.
+    pub const fn new(minimize_weight_above: FeeRate, consolidate_below: FeeRate) -> Self {
+        AutoPolicy {
+            minimize_weight_above,
+            consolidate_below,
+        }
+    }
+
+    /// Returns the strategy to use at `fee_rate`.
+    ///
+    /// If the thresholds overlap, minimizing weight takes precedence.
+    pub fn strategy(&self, fee_rate: FeeRate) -> Strategy {
+        if fee_rate > self.minimize_weight_above {
+            Strategy::MinimizeWeight
+        } else if fee_rate < self.consolidate_below {
+            Strategy::Consolidate
+        } else {
+            Strategy::Standard
+        }
+    }
+}
+
+impl Default for AutoPolicy {
+    fn default() -> Self {
+        AutoPolicy::new(defaults::MINIMIZE_WEIGHT_ABOVE, defaults::CONSOLIDATE_BELOW)
+    }
+}
+
+/// Strategy and fee rates to use for a payment with a given confirmation target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfTargetPreset {
+    /// The selection strategy.
+    pub strategy: Strategy,
+    /// The fee rate of the transaction.
+    pub fee_rate: FeeRate,
+    /// The long term fee rate, used to value spending change later.
+    pub long_term_fee_rate: FeeRate,
+}
+
+/// Chooses a [`Strategy`] from the urgency of a payment.
+///
+/// Urgent payments minimize weight, since every input is paid for at a high rate. Payments
+/// that can wait are a good opportunity to consolidate. Everything in between is decided
+/// by the fee rate, see [`AutoPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfTargetPolicy {
+    /// Confirmation targets up to this many blocks are urgent. Defaults to 2.
+    pub urgent_within: u16,
+    /// Confirmation targets of at least this many blocks are relaxed. Defaults to 144,
+    /// about a day.
+    pub relaxed_from: u16,
+    /// Used for confirmation targets that are neither urgent nor relaxed.
+    pub fallback: AutoPolicy,
+}
+
+impl ConfTargetPolicy {
+    /// Returns the strategy and fee rates for a payment that should confirm within
+    /// `conf_target` blocks, using `estimator` to resolve the fee rates.
+    pub fn preset<E: FeeEstimator>(&self, conf_target: u16, estimator: E) -> ConfTargetPreset {
+        let fee_rate = estimator.fee_rate(conf_target);
+        let strategy = if conf_target <= self.urgent_within {
+            Strategy::MinimizeWeight
+        } else if conf_target >= self.relaxed_from {
+            Strategy::Consolidate
+        } else {
+            self.fallback.strategy(fee_rate)
+        };
+
+        ConfTargetPreset {
+            strategy,
+            fee_rate,
+            long_term_fee_rate: estimator.long_term_fee_rate(),
+        }
+    }
+}
+
+impl Default for ConfTargetPolicy {
+    fn default() -> Self {
+        ConfTargetPolicy {
+            urgent_within: defaults::URGENT_WITHIN,
+            relaxed_from: defaults::RELAXED_FROM,
+            fallback: AutoPolicy::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estimator::StaticFeeEstimator;
+
+    fn sat_vb(n: u32) -> FeeRate {
+        FeeRate::from_sat_per_vb_u32(n)
+    }
+
+    #[test]
+    fn default_thresholds() {
+        let policy = AutoPolicy::default();
+        assert_eq!(policy.strategy(sat_vb(1)), Strategy::Consolidate);
+        assert_eq!(policy.strategy(sat_vb(3)), Strategy::Standard);
+        assert_eq!(policy.strategy(sat_vb(10)), Strategy::Standard);
+        assert_eq!(policy.strategy(sat_vb(30)), Strategy::Standard);
+        assert_eq!(policy.strategy(sat_vb(31)), Strategy::MinimizeWeight);
+    }
+
+    #[test]
+    fn custom_thresholds() {
+        let policy = AutoPolicy::new(sat_vb(10), sat_vb(5));
+        assert_eq!(policy.strategy(sat_vb(4)), Strategy::Consolidate);
+        assert_eq!(policy.strategy(sat_vb(7)), Strategy::Standard);
+        assert_eq!(policy.strategy(sat_vb(11)), Strategy::MinimizeWeight);
+    }
+
+    #[test]
+    fn overlapping_thresholds_prefer_weight() {
+        let policy = AutoPolicy::new(sat_vb(5), sat_vb(10));
+        assert_eq!(policy.strategy(sat_vb(7)), Strategy::MinimizeWeight);
+        assert_eq!(policy.strategy(sat_vb(4)), Strategy::Consolidate);
+    }
+
+    #[test]
+    fn conf_target_presets() {
+        let policy = ConfTargetPolicy::default();
+        let estimator = StaticFeeEstimator {
+            fee_rate: sat_vb(10),
+            long_term_fee_rate: sat_vb(5),
+        };
+
+        let urgent = policy.preset(1, estimator);
+        assert_eq!(
+            urgent,
+            ConfTargetPreset {
+                strategy: Strategy::MinimizeWeight,
+                fee_rate: sat_vb(10),
+                long_term_fee_rate: sat_vb(5),
+            }
+        );
+        assert_eq!(policy.preset(6, estimator).strategy, Strategy::Standard);
+        assert_eq!(
+            policy.preset(144, estimator).strategy,
+            Strategy::Consolidate
+        );
+    }
+
+    #[test]
+    fn conf_target_fallback_uses_fee_rate() {
+        let policy = ConfTargetPolicy::default();
+        let expensive = StaticFeeEstimator {
+            fee_rate: sat_vb(50),
+            long_term_fee_rate: sat_vb(5),
+        };
+        let cheap = StaticFeeEstimator {
+            fee_rate: sat_vb(1),
+            long_term_fee_rate: sat_vb(5),
+        };
+
+        assert_eq!(
+            policy.preset(6, expensive).strategy,
+            Strategy::MinimizeWeight
+        );
+        assert_eq!(policy.preset(6, cheap).strategy, Strategy::Consolidate);
+    }
+}
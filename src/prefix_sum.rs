@@ -0,0 +1,227 @@
+//! Prefix-sum nearest subset heuristic.
+//!
+//! The largest candidates are taken until their effective values cover the target, and the
+//! last one taken is then replaced by the smallest candidate that still closes the gap.
+//! Both steps are binary searches over the sorted pool, so the selection runs in
+//! O(n log n). It is not optimal, but it is fast and predictable, which makes it a useful
+//! fallback when a search gives up and a starting point for one.
+
+use bitcoin::{Amount, FeeRate};
+
+use crate::candidate::{eligible_candidates, Candidate};
+use crate::order::sort_candidates;
+use crate::target::check_target;
+use crate::{effective_value, SelectionError, SelectionReport, WeightedUtxo};
+
+/// Selects the fewest of the largest candidates covering `target`, tightening the last one.
+///
+/// Candidates are sorted by descending effective value at `fee_rate` and the shortest
+/// prefix whose sum covers `target` is found. Its last candidate is swapped for the
+/// candidate with the smallest effective value among those not in the prefix that still
+/// covers the remainder, preferring the lightest on ties.
+///
+/// The selection is in canonical order and the report counts no iterations.
+pub fn select_coins_prefix_sum<U: WeightedUtxo>(
+    target: Amount,
+    fee_rate: FeeRate,
+    pool: &[U],
+) -> Result<SelectionReport<'_, U>, SelectionError> {
+    check_target(target)?;
+    select_from(target, eligible_candidates(pool, fee_rate, |_| {}))
+}
+
+/// Runs [`select_coins_prefix_sum`] at each of `fee_rates`, returning a result per rate.
+///
+/// Fee bumping evaluates the same pool at many fee rates. The spendability and weight of
+/// each candidate are computed once and shared by all rates, so only the effective values
+/// and the order are worked out again per rate. Each result equals the one of a separate
+/// call at that rate.
+pub fn select_at_feerates<'a, U: WeightedUtxo>(
+    target: Amount,
+    fee_rates: &[FeeRate],
+    pool: &'a [U],
+) -> Vec<Result<SelectionReport<'a, U>, SelectionError>> {
+    // Candidates dropped at every rate are dropped here, the rest wait for a rate.
+    let prepared: Vec<_> = pool
+        .iter()
+        .enumerate()
+        .filter(|(_, utxo)| utxo.is_spendable())
+        .filter_map(|(index, utxo)| Some((index, utxo, utxo.weight()?, utxo.fee_adjustment())))
+        .collect();
+
+    fee_rates
+        .iter()
+        .map(|&fee_rate| {
+            check_target(target)?;
+            let candidates = prepared
+                .iter()
+                .filter_map(|&(index, utxo, weight, fee_adjustment)| {
+                    let effective_value = effective_value(fee_rate, weight, utxo.value())?
+                        .checked_sub(utxo.bump_fee(fee_rate)?.to_signed().ok()?)?
+                        .checked_add(fee_adjustment)?;
+                    effective_value.is_positive().then(|| Candidate {
+                        index,
+                        utxo,
+                        effective_value: effective_value.unsigned_abs(),
+                        weight,
+                    })
+                })
+                .collect();
+            select_from(target, candidates)
+        })
+        .collect()
+}
+
+/// Selects from prepared `candidates` as [`select_coins_prefix_sum`] does.
+fn select_from<U: WeightedUtxo>(
+    target: Amount,
+    mut candidates: Vec<Candidate<'_, U>>,
+) -> Result<SelectionReport<'_, U>, SelectionError> {
+    sort_candidates(&mut candidates);
+
+    // Sums only need to be compared against the target, so saturating is exact enough.
+    let prefix: Vec<Amount> = candidates
+        .iter()
+        .scan(Amount::ZERO, |sum, c| {
+            *sum = sum.checked_add(c.effective_value).unwrap_or(Amount::MAX);
+            Some(*sum)
+        })
+        .collect();
+
+    let last = prefix.partition_point(|&sum| sum < target);
+    if last == prefix.len() {
+        return Err(SelectionError::InsufficientFunds {
+            available: prefix.last().copied().unwrap_or(Amount::ZERO),
+            required: target,
+        });
+    }
+
+    let covered = if last == 0 {
+        Amount::ZERO
+    } else {
+        prefix[last - 1]
+    };
+    let remainder = target - covered;
+
+    // The tail is sorted by descending effective value and its first candidate covers the
+    // remainder, so the tightest fit is the first of the last run covering it.
+    let tail = &candidates[last..];
+    let tightest =
+        tail[tail.partition_point(|c| c.effective_value >= remainder) - 1].effective_value;
+    let replacement = tail[tail.partition_point(|c| c.effective_value > tightest)];
+
+    let mut selection = candidates[..last].to_vec();
+    selection.push(replacement);
+    SelectionReport::new(selection, target, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_pool;
+
+    fn indices<U>(
+        report: Result<SelectionReport<'_, U>, SelectionError>,
+    ) -> Result<Vec<usize>, SelectionError> {
+        report.map(|r| r.selection.iter().map(|c| c.index).collect())
+    }
+
+    #[test]
+    fn largest_prefix_covers_target() {
+        let pool = build_pool(&[(1_000, 0), (5_000, 0), (3_000, 0)]);
+        let selection = select_coins_prefix_sum(Amount::from_sat(8_000), FeeRate::ZERO, &pool);
+        assert_eq!(indices(selection), Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn last_candidate_is_tightened() {
+        // 5 000 leaves 1 000 to cover, which the 1 200 candidate does instead of 4 000.
+        let pool = build_pool(&[(5_000, 0), (4_000, 0), (1_200, 0), (900, 0)]);
+        let selection = select_coins_prefix_sum(Amount::from_sat(6_000), FeeRate::ZERO, &pool);
+        assert_eq!(indices(selection), Ok(vec![0, 2]));
+    }
+
+    #[test]
+    fn lightest_replacement_on_ties() {
+        let pool = build_pool(&[(5_000, 0), (4_000, 0), (1_200, 272), (1_200, 112)]);
+        let selection = select_coins_prefix_sum(Amount::from_sat(6_000), FeeRate::ZERO, &pool);
+        assert_eq!(indices(selection), Ok(vec![0, 3]));
+    }
+
+    #[test]
+    fn single_candidate_replaced() {
+        let pool = build_pool(&[(9_000, 0), (2_500, 0), (2_000, 0)]);
+        let selection = select_coins_prefix_sum(Amount::from_sat(2_100), FeeRate::ZERO, &pool);
+        assert_eq!(indices(selection), Ok(vec![1]));
+    }
+
+    #[test]
+    fn effective_values_are_used() {
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        // 272 wu at 10 sat/vB costs 1080 sats, leaving 1 920 and 920.
+        let pool = build_pool(&[(3_000, 272), (2_000, 272), (900, 272)]);
+        let selection = select_coins_prefix_sum(Amount::from_sat(2_000), fee_rate, &pool);
+        assert_eq!(indices(selection), Ok(vec![0, 1]));
+    }
+
+    #[test]
+    fn huge_values() {
+        let huge = i64::MAX as u64;
+        let pool = build_pool(&[(huge, 0), (huge, 0), (1, 0)]);
+        let selection = select_coins_prefix_sum(Amount::from_sat(huge), FeeRate::ZERO, &pool);
+        assert_eq!(indices(selection), Ok(vec![0]));
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        let pool = build_pool(&[(1_000, 0), (2_000, 0)]);
+        let selection = select_coins_prefix_sum(Amount::from_sat(3_001), FeeRate::ZERO, &pool);
+        assert_eq!(
+            indices(selection),
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(3_000),
+                required: Amount::from_sat(3_001),
+            })
+        );
+    }
+
+    #[test]
+    fn zero_target() {
+        let pool = build_pool(&[(1_000, 0)]);
+        let selection = select_coins_prefix_sum(Amount::ZERO, FeeRate::ZERO, &pool);
+        assert_eq!(
+            indices(selection),
+            Err(SelectionError::InvalidTarget(
+                crate::target::InvalidTargetError::Zero
+            ))
+        );
+    }
+
+    #[test]
+    fn at_feerates_match_separate_calls() {
+        // At 50 sat/vB the 272 wu candidates cost 3 400 sats and the last one drops out.
+        let pool = build_pool(&[
+            (9_000, 272),
+            (6_000, 112),
+            (4_000, 272),
+            (3_000, 272),
+            (2_500, 0),
+        ]);
+        let fee_rates: Vec<_> = [0, 1, 10, 50, 200]
+            .iter()
+            .map(|&r| FeeRate::from_sat_per_vb_u32(r))
+            .collect();
+
+        for &target in &[0, 5_000, 12_000, 30_000] {
+            let target = Amount::from_sat(target);
+            let results = select_at_feerates(target, &fee_rates, &pool);
+            assert_eq!(results.len(), fee_rates.len());
+            for (result, &fee_rate) in results.into_iter().zip(&fee_rates) {
+                assert_eq!(
+                    indices(result),
+                    indices(select_coins_prefix_sum(target, fee_rate, &pool))
+                );
+            }
+        }
+    }
+}
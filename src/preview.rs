@@ -0,0 +1,117 @@
+//! Previewing the effect of a spend on the pool.
+//!
+//! A selection changes more than the balance: it shrinks the number of coins and with it
+//! the fees future transactions pay. [`preview_spend`] computes the pool left after a
+//! selection and how much of it can still be spent now and at the long term fee rate, so
+//! a wallet can show the structural impact of a spend before signing it.
+
+use bitcoin::{Amount, FeeRate};
+
+use crate::candidate::eligible_candidates;
+use crate::WeightedUtxo;
+
+/// The pool after a spend, with the balances before and after it.
+///
+/// The spendable balances are the sums of the positive effective values, what the pool
+/// can pay at the fee rate.
+#[derive(Debug)]
+pub struct SpendPreview<'a, U> {
+    /// The candidates that are not spent with their pool indices, in pool order.
+    pub remaining: Vec<(usize, &'a U)>,
+    /// Number of candidates spent, by which the pool shrinks before adding change.
+    pub spent: usize,
+    /// Spendable balance of the pool at the fee rate before the spend.
+    pub spendable_before: Amount,
+    /// Spendable balance of the remaining candidates at the fee rate.
+    pub spendable_after: Amount,
+    /// Spendable balance of the pool at the long term fee rate before the spend.
+    pub long_term_spendable_before: Amount,
+    /// Spendable balance of the remaining candidates at the long term fee rate.
+    pub long_term_spendable_after: Amount,
+}
+
+impl<U> Clone for SpendPreview<'_, U> {
+    fn clone(&self) -> Self {
+        SpendPreview {
+            remaining: self.remaining.clone(),
+            ..*self
+        }
+    }
+}
+
+/// Previews spending the candidates of `pool` at the indices in `selection`.
+///
+/// Duplicate indices are spent once. Returns `None` if an index is out of bounds or a
+/// balance overflows.
+pub fn preview_spend<'a, U: WeightedUtxo>(
+    pool: &'a [U],
+    selection: &[usize],
+    fee_rate: FeeRate,
+    long_term_fee_rate: FeeRate,
+) -> Option<SpendPreview<'a, U>> {
+    let mut spent = vec![false; pool.len()];
+    for &index in selection {
+        *spent.get_mut(index)? = true;
+    }
+
+    let balances = |fee_rate| {
+        let (mut before, mut after) = (Amount::ZERO, Amount::ZERO);
+        for candidate in eligible_candidates(pool, fee_rate, |_| {}) {
+            before = before.checked_add(candidate.effective_value)?;
+            if !spent[candidate.index] {
+                after = after.checked_add(candidate.effective_value)?;
+            }
+        }
+        Some((before, after))
+    };
+    let (spendable_before, spendable_after) = balances(fee_rate)?;
+    let (long_term_spendable_before, long_term_spendable_after) = balances(long_term_fee_rate)?;
+
+    let remaining: Vec<(usize, &U)> = pool
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| !spent[index])
+        .collect();
+    Some(SpendPreview {
+        spent: pool.len() - remaining.len(),
+        remaining,
+        spendable_before,
+        spendable_after,
+        long_term_spendable_before,
+        long_term_spendable_after,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_pool;
+
+    #[test]
+    fn spend_preview() {
+        // At 10 sat/vB each 432 wu input costs 1 080 sats, more than candidate 1 is worth, and
+        // at 1 sat/vB 108.
+        let pool = build_pool(&[(50_000, 272), (1_000, 272), (20_000, 272), (3_000, 272)]);
+        let preview = preview_spend(
+            &pool,
+            &[2, 0, 2],
+            FeeRate::from_sat_per_vb_u32(10),
+            FeeRate::from_sat_per_vb_u32(1),
+        )
+        .unwrap();
+
+        let remaining: Vec<usize> = preview.remaining.iter().map(|&(i, _)| i).collect();
+        assert_eq!(remaining, vec![1, 3]);
+        assert_eq!(preview.spent, 2);
+        assert_eq!(preview.spendable_before, Amount::from_sat(69_760));
+        assert_eq!(preview.spendable_after, Amount::from_sat(1_920));
+        assert_eq!(preview.long_term_spendable_before, Amount::from_sat(73_568));
+        assert_eq!(preview.long_term_spendable_after, Amount::from_sat(3_784));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let pool = build_pool(&[(50_000, 272)]);
+        assert!(preview_spend(&pool, &[1], FeeRate::ZERO, FeeRate::ZERO).is_none());
+    }
+}
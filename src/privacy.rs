@@ -0,0 +1,202 @@
+//! Privacy heuristics for selections.
+//!
+//! Some selections leak more about the wallet than others. Spending many inputs, spending
+//! only some of the coins sent to an address, or mixing script types all link coins
+//! together for chain observers, and change with an unround value is easy to tell apart
+//! from the payment. [`privacy_score`] summarizes these so alternative selections can be
+//! ranked, and [`CoinSelector::select_with_privacy_score`] attaches the score to the
+//! report.
+//!
+//! [`CoinSelector::select_with_privacy_score`]: crate::CoinSelector::select_with_privacy_score
+
+use std::collections::BTreeSet;
+
+use bitcoin::{Amount, Script};
+
+use crate::UtxoMetadata;
+
+/// Change values that are a multiple of this are considered round.
+pub const ROUND_CHANGE_GRANULARITY: Amount = Amount::from_sat(10_000);
+
+/// Privacy properties of a selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrivacyScore {
+    /// Number of selected inputs.
+    pub input_count: usize,
+    /// Number of selected inputs sharing their script with an unselected candidate.
+    pub reused_scripts: usize,
+    /// Number of distinct script types among the selected inputs.
+    pub script_types: usize,
+    /// Whether the change is a multiple of [`ROUND_CHANGE_GRANULARITY`], `None` without
+    /// change.
+    pub round_change: Option<bool>,
+}
+
+impl PrivacyScore {
+    /// A single number summarizing the score, lower is better.
+    ///
+    /// Each input beyond the first adds 1, each reused script 2, each script type beyond
+    /// the first 3 and unround change 1.
+    pub fn penalty(&self) -> usize {
+        self.input_count.saturating_sub(1)
+            + 2 * self.reused_scripts
+            + 3 * self.script_types.saturating_sub(1)
+            + usize::from(self.round_change == Some(false))
+    }
+}
+
+/// Scores the privacy of selecting the candidates at `selection` from `pool`.
+///
+/// `selection` holds indices into `pool`. The rest of `pool` is the context the selection
+/// is judged against: a selected input whose script also locks an unselected candidate
+/// links the two once spent. `change` is the value of the change output, if any.
+///
+/// # Panics
+///
+/// If an index in `selection` is out of bounds.
+pub fn privacy_score<U: UtxoMetadata>(
+    selection: &[usize],
+    pool: &[U],
+    change: Option<Amount>,
+) -> PrivacyScore {
+    let selected: BTreeSet<usize> = selection.iter().copied().collect();
+
+    let reused_scripts = selected
+        .iter()
+        .filter(|&&index| {
+            let script = pool[index].script_pubkey();
+            pool.iter()
+                .enumerate()
+                .any(|(i, utxo)| !selected.contains(&i) && utxo.script_pubkey() == script)
+        })
+        .count();
+
+    let script_types = selected
+        .iter()
+        .map(|&index| ScriptType::of(pool[index].script_pubkey()))
+        .collect::<BTreeSet<_>>()
+        .len();
+
+    PrivacyScore {
+        input_count: selected.len(),
+        reused_scripts,
+        script_types,
+        round_change: change.map(|change| change.to_sat() % ROUND_CHANGE_GRANULARITY.to_sat() == 0),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    Other,
+}
+
+impl ScriptType {
+    fn of(script: &Script) -> Self {
+        if script.is_p2pkh() {
+            ScriptType::P2pkh
+        } else if script.is_p2sh() {
+            ScriptType::P2sh
+        } else if script.is_p2wpkh() {
+            ScriptType::P2wpkh
+        } else if script.is_p2wsh() {
+            ScriptType::P2wsh
+        } else if script.is_p2tr() {
+            ScriptType::P2tr
+        } else {
+            ScriptType::Other
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{OutPoint, PubkeyHash, ScriptBuf, WPubkeyHash};
+
+    use super::*;
+
+    struct Coin(ScriptBuf);
+
+    impl UtxoMetadata for Coin {
+        fn outpoint(&self) -> OutPoint {
+            OutPoint::null()
+        }
+
+        fn script_pubkey(&self) -> &Script {
+            &self.0
+        }
+    }
+
+    fn p2wpkh(byte: u8) -> Coin {
+        Coin(ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array(
+            [byte; 20],
+        )))
+    }
+
+    fn p2pkh(byte: u8) -> Coin {
+        Coin(ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array(
+            [byte; 20],
+        )))
+    }
+
+    #[test]
+    fn single_input_without_change() {
+        let pool = [p2wpkh(1), p2wpkh(2)];
+        let score = privacy_score(&[1], &pool, None);
+        assert_eq!(
+            score,
+            PrivacyScore {
+                input_count: 1,
+                reused_scripts: 0,
+                script_types: 1,
+                round_change: None,
+            }
+        );
+        assert_eq!(score.penalty(), 0);
+    }
+
+    #[test]
+    fn partially_spent_script_is_reuse() {
+        let pool = [p2wpkh(1), p2wpkh(1), p2wpkh(1), p2wpkh(2)];
+
+        let partial = privacy_score(&[0, 3], &pool, None);
+        assert_eq!(partial.reused_scripts, 1);
+
+        let whole = privacy_score(&[0, 1, 2], &pool, None);
+        assert_eq!(whole.reused_scripts, 0);
+    }
+
+    #[test]
+    fn script_type_mix() {
+        let pool = [p2wpkh(1), p2pkh(2), p2wpkh(3)];
+        assert_eq!(privacy_score(&[0, 2], &pool, None).script_types, 1);
+        assert_eq!(privacy_score(&[0, 1], &pool, None).script_types, 2);
+    }
+
+    #[test]
+    fn round_change() {
+        let pool = [p2wpkh(1)];
+        let round = privacy_score(&[0], &pool, Some(Amount::from_sat(50_000)));
+        let unround = privacy_score(&[0], &pool, Some(Amount::from_sat(48_211)));
+        assert_eq!(round.round_change, Some(true));
+        assert_eq!(unround.round_change, Some(false));
+        assert!(round.penalty() < unround.penalty());
+    }
+
+    #[test]
+    fn penalty() {
+        let score = PrivacyScore {
+            input_count: 3,
+            reused_scripts: 1,
+            script_types: 2,
+            round_change: Some(false),
+        };
+        assert_eq!(score.penalty(), 2 + 2 + 3 + 1);
+    }
+}
@@ -0,0 +1,118 @@
+//! Filling a PSBT with a selection.
+//!
+//! Once the inputs are chosen, every wallet adds them to its transaction the same way: an
+//! input spending each outpoint, the spent output as the witness UTXO for the signer, and
+//! possibly a change output. [`populate_psbt`] does that.
+
+use bitcoin::psbt::{Input, Output, Psbt};
+use bitcoin::{ScriptBuf, Sequence, TxIn, TxOut, Witness};
+
+use crate::{UtxoMetadata, WeightedUtxo};
+
+/// Adds an input spending each candidate of `selection` to `psbt`, then `change`, if any,
+/// as the last output.
+///
+/// Every input gets `sequence` and the spent output, rebuilt from the value and script
+/// of the candidate, as its witness UTXO. Existing inputs and outputs are kept.
+pub fn populate_psbt<'a, U, I>(
+    psbt: &mut Psbt,
+    selection: I,
+    sequence: Sequence,
+    change: Option<TxOut>,
+) where
+    U: WeightedUtxo + UtxoMetadata + 'a,
+    I: IntoIterator<Item = &'a U>,
+{
+    for utxo in selection {
+        psbt.unsigned_tx.input.push(TxIn {
+            previous_output: utxo.outpoint(),
+            script_sig: ScriptBuf::new(),
+            sequence,
+            witness: Witness::new(),
+        });
+        psbt.inputs.push(Input {
+            witness_utxo: Some(TxOut {
+                value: utxo.value(),
+                script_pubkey: utxo.script_pubkey().to_owned(),
+            }),
+            ..Input::default()
+        });
+    }
+
+    if let Some(change) = change {
+        psbt.unsigned_tx.output.push(change);
+        psbt.outputs.push(Output::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, OutPoint, Transaction, Txid, WPubkeyHash};
+
+    use super::*;
+    use crate::utxo::Utxo;
+
+    fn output(value: u64, byte: u8) -> TxOut {
+        TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([byte; 20])),
+        }
+    }
+
+    #[test]
+    fn inputs_and_change() {
+        let pool: Vec<Utxo> = (0..2)
+            .map(|vout| {
+                let outpoint = OutPoint::new(Txid::all_zeros(), vout);
+                Utxo::from_txout(outpoint, output(10_000 * u64::from(vout + 1), 1)).unwrap()
+            })
+            .collect();
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: Vec::new(),
+            output: vec![output(25_000, 2)],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+
+        populate_psbt(
+            &mut psbt,
+            &pool,
+            Sequence::ENABLE_RBF_NO_LOCKTIME,
+            Some(output(4_000, 3)),
+        );
+
+        assert_eq!(psbt.unsigned_tx.input.len(), 2);
+        assert_eq!(psbt.inputs.len(), 2);
+        for (utxo, (txin, input)) in pool
+            .iter()
+            .zip(psbt.unsigned_tx.input.iter().zip(&psbt.inputs))
+        {
+            assert_eq!(txin.previous_output, utxo.outpoint);
+            assert_eq!(txin.sequence, Sequence::ENABLE_RBF_NO_LOCKTIME);
+            assert_eq!(input.witness_utxo.as_ref(), Some(&utxo.txout));
+        }
+        assert_eq!(
+            psbt.unsigned_tx.output,
+            vec![output(25_000, 2), output(4_000, 3)]
+        );
+        assert_eq!(psbt.outputs.len(), 2);
+    }
+
+    #[test]
+    fn without_change() {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: Vec::new(),
+            output: vec![output(25_000, 2)],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        populate_psbt(&mut psbt, &Vec::<Utxo>::new(), Sequence::MAX, None);
+        assert_eq!(psbt.unsigned_tx.output.len(), 1);
+        assert_eq!(psbt.outputs.len(), 1);
+    }
+}
@@ -0,0 +1,269 @@
+//! Repairing a selection after some of its inputs disappeared.
+//!
+//! When a broadcast fails because one of the inputs was spent concurrently, starting the
+//! selection over may pick a completely different input set. [`repair_selection`] instead
+//! keeps the inputs that are still valid and replaces only the missing value.
+
+use std::collections::BTreeMap;
+
+use bitcoin::{Amount, FeeRate, OutPoint, SignedAmount};
+
+use crate::candidate::eligible_candidates;
+use crate::order::sort_canonical;
+use crate::target::check_target;
+use crate::{SelectionError, UtxoMetadata, WeightedUtxo};
+
+/// Repairs `previous` after the outputs in `missing_outpoints` became unavailable.
+///
+/// `previous` holds indices into `pool`, while the outpoints are what a failed broadcast
+/// reports. The inputs of `previous` whose outpoints aren't missing are kept and, if their
+/// effective values no longer cover `target`, topped up from the candidates neither in
+/// `previous` nor missing. The top-up prefers the single candidate with
+/// the smallest effective value covering the shortfall. If no single candidate does,
+/// candidates are added by descending effective value until it is covered. Inputs of
+/// `previous` that [`eligible_candidates`] would drop, such as frozen ones, are not kept,
+/// and such candidates are never added.
+///
+/// Returns the pool indices of the repaired selection in canonical order. Indices repeated
+/// in `previous` are selected once.
+///
+/// # Errors
+///
+/// [`SelectionError::InvalidTarget`] if `target` is zero,
+/// [`SelectionError::InvalidPresetInput`] if an index of `previous` is not in `pool`, and
+/// [`SelectionError::InsufficientFunds`] if the available candidates can't cover `target`.
+pub fn repair_selection<U: WeightedUtxo + UtxoMetadata>(
+    pool: &[U],
+    previous: &[usize],
+    missing_outpoints: &[OutPoint],
+    target: Amount,
+    fee_rate: FeeRate,
+) -> Result<Vec<usize>, SelectionError> {
+    check_target(target)?;
+    if let Some(&index) = previous.iter().find(|&&i| i >= pool.len()) {
+        return Err(SelectionError::InvalidPresetInput(index));
+    }
+    let eligible: BTreeMap<usize, Amount> = eligible_candidates(pool, fee_rate, |_| {})
+        .into_iter()
+        .filter(|c| !missing_outpoints.contains(&c.utxo.outpoint()))
+        .map(|c| (c.index, c.effective_value))
+        .collect();
+
+    let mut selection: Vec<usize> = previous
+        .iter()
+        .copied()
+        .filter(|i| eligible.contains_key(i))
+        .collect();
+    selection.sort_unstable();
+    selection.dedup();
+
+    let mut shortfall = target.to_signed().map_err(|_| SelectionError::Overflow)?;
+    for index in &selection {
+        shortfall = signed(eligible[index])
+            .and_then(|value| shortfall.checked_sub(value))
+            .ok_or(SelectionError::Overflow)?;
+    }
+
+    if shortfall.is_positive() {
+        let mut available: Vec<(usize, SignedAmount)> = eligible
+            .iter()
+            .filter(|(i, _)| !previous.contains(i))
+            .map(|(&i, &value)| Some((i, signed(value)?)))
+            .collect::<Option<_>>()
+            .ok_or(SelectionError::Overflow)?;
+        available.sort_by_key(|&(i, value)| (value, i));
+
+        match available.iter().find(|(_, value)| *value >= shortfall) {
+            Some(&(index, _)) => selection.push(index),
+            None => {
+                for &(index, value) in available.iter().rev() {
+                    selection.push(index);
+                    shortfall -= value;
+                    if !shortfall.is_positive() {
+                        break;
+                    }
+                }
+                if shortfall.is_positive() {
+                    let available = eligible
+                        .values()
+                        .try_fold(Amount::ZERO, |sum, &value| sum.checked_add(value))
+                        .ok_or(SelectionError::Overflow)?;
+                    return Err(SelectionError::InsufficientFunds {
+                        available,
+                        required: target,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut ordered: Vec<(usize, &U)> = selection.into_iter().map(|i| (i, &pool[i])).collect();
+    sort_canonical(&mut ordered, fee_rate);
+    Ok(ordered.into_iter().map(|(i, _)| i).collect())
+}
+
+fn signed(value: Amount) -> Option<SignedAmount> {
+    value.to_signed().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{Script, Txid, Weight};
+
+    use super::*;
+    use crate::target::InvalidTargetError;
+
+    const FEE_RATE: FeeRate = FeeRate::ZERO;
+
+    struct Coin {
+        value: u64,
+        satisfaction_weight: u64,
+        frozen: bool,
+        vout: u32,
+    }
+
+    impl WeightedUtxo for Coin {
+        fn satisfaction_weight(&self) -> Weight {
+            Weight::from_wu(self.satisfaction_weight)
+        }
+        fn value(&self) -> Amount {
+            Amount::from_sat(self.value)
+        }
+        fn is_spendable(&self) -> bool {
+            !self.frozen
+        }
+    }
+
+    impl UtxoMetadata for Coin {
+        fn outpoint(&self) -> OutPoint {
+            outpoint(self.vout)
+        }
+        fn script_pubkey(&self) -> &Script {
+            Script::new()
+        }
+    }
+
+    fn outpoint(vout: u32) -> OutPoint {
+        OutPoint::new(Txid::all_zeros(), vout)
+    }
+
+    /// Builds a pool of `(value, satisfaction weight)` coins spending output `i` of a
+    /// transaction.
+    fn build_pool(coins: &[(u64, u64)]) -> Vec<Coin> {
+        (0..)
+            .zip(coins)
+            .map(|(vout, &(value, satisfaction_weight))| Coin {
+                value,
+                satisfaction_weight,
+                frozen: false,
+                vout,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nothing_missing() {
+        let pool = build_pool(&[(5_000, 0), (3_000, 0), (1_000, 0)]);
+        let repaired = repair_selection(&pool, &[0, 2], &[], Amount::from_sat(6_000), FEE_RATE);
+        assert_eq!(repaired, Ok(vec![0, 2]));
+    }
+
+    #[test]
+    fn smallest_single_replacement() {
+        let pool = build_pool(&[(5_000, 0), (3_000, 0), (1_000, 0), (2_500, 0), (9_000, 0)]);
+        let missing = [outpoint(1)];
+        let repaired =
+            repair_selection(&pool, &[0, 1], &missing, Amount::from_sat(7_000), FEE_RATE);
+        assert_eq!(repaired, Ok(vec![0, 3]));
+    }
+
+    #[test]
+    fn multiple_replacements() {
+        let pool = build_pool(&[(5_000, 0), (3_000, 0), (1_000, 0), (1_500, 0), (800, 0)]);
+        let missing = [outpoint(1)];
+        let repaired =
+            repair_selection(&pool, &[0, 1], &missing, Amount::from_sat(7_800), FEE_RATE);
+        assert_eq!(repaired, Ok(vec![0, 3, 2, 4]));
+    }
+
+    #[test]
+    fn missing_candidates_are_not_reused() {
+        let pool = build_pool(&[(5_000, 0), (3_000, 0)]);
+        let missing = [outpoint(0)];
+        let repaired = repair_selection(&pool, &[0], &missing, Amount::from_sat(4_000), FEE_RATE);
+        assert_eq!(
+            repaired,
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(3_000),
+                required: Amount::from_sat(4_000),
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_outpoints_are_ignored() {
+        let pool = build_pool(&[(5_000, 0), (3_000, 0)]);
+        let missing = [outpoint(7)];
+        let repaired = repair_selection(&pool, &[0], &missing, Amount::from_sat(4_000), FEE_RATE);
+        assert_eq!(repaired, Ok(vec![0]));
+    }
+
+    #[test]
+    fn insufficient_pool() {
+        let pool = build_pool(&[(5_000, 0), (3_000, 0), (1_000, 0)]);
+        let missing = [outpoint(1)];
+        let repaired =
+            repair_selection(&pool, &[0, 1], &missing, Amount::from_sat(7_000), FEE_RATE);
+        assert_eq!(
+            repaired,
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(6_000),
+                required: Amount::from_sat(7_000),
+            })
+        );
+    }
+
+    #[test]
+    fn duplicate_previous_indices_count_once() {
+        let pool = build_pool(&[(5_000, 0), (3_000, 0), (1_000, 0)]);
+        let repaired = repair_selection(&pool, &[0, 0], &[], Amount::from_sat(8_000), FEE_RATE);
+        assert_eq!(repaired, Ok(vec![0, 1]));
+    }
+
+    #[test]
+    fn uneconomical_candidates_are_skipped() {
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        // 272 wu at 10 sat/vB costs 1080 sats.
+        let pool = build_pool(&[(10_000, 272), (5_000, 272), (1_000, 272), (4_000, 272)]);
+        let missing = [outpoint(1)];
+        let repaired =
+            repair_selection(&pool, &[0, 1], &missing, Amount::from_sat(11_000), fee_rate);
+        assert_eq!(repaired, Ok(vec![0, 3]));
+    }
+
+    #[test]
+    fn frozen_candidates_are_dropped() {
+        let mut pool = build_pool(&[(5_000, 0), (3_000, 0), (4_000, 0), (2_000, 0), (1_500, 0)]);
+        pool[1].frozen = true;
+        pool[2].frozen = true;
+        let repaired = repair_selection(&pool, &[0, 1], &[], Amount::from_sat(8_000), FEE_RATE);
+        assert_eq!(repaired, Ok(vec![0, 3, 4]));
+    }
+
+    #[test]
+    fn invalid_previous_index() {
+        let pool = build_pool(&[(5_000, 0)]);
+        let repaired = repair_selection(&pool, &[3], &[], Amount::from_sat(1_000), FEE_RATE);
+        assert_eq!(repaired, Err(SelectionError::InvalidPresetInput(3)));
+    }
+
+    #[test]
+    fn zero_target() {
+        let pool = build_pool(&[(5_000, 0)]);
+        assert_eq!(
+            repair_selection(&pool, &[0], &[], Amount::ZERO, FEE_RATE),
+            Err(SelectionError::InvalidTarget(InvalidTargetError::Zero))
+        );
+    }
+}
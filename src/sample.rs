@@ -0,0 +1,80 @@
+//! Sampling pools too large to search.
+//!
+//! A depth first search spends at least one iteration per candidate on its first path, so
+//! once a pool has more candidates than the search has iterations it can't explore
+//! anything. Searching a sample of the pool instead degrades the result gracefully: the
+//! largest candidates are kept, since few of them cover most targets, and the rest is
+//! represented by candidates spread evenly over its range.
+
+/// Pools with more candidates than this are sampled before searching, the iteration limit
+/// of [`coin_grinder`](crate::coin_grinder).
+pub const SAMPLE_THRESHOLD: usize = crate::coin_grinder::ITERATION_LIMIT;
+
+/// Number of candidates in a sample.
+pub const SAMPLE_SIZE: usize = 10_000;
+
+/// Returns at most `size` of `sorted`, which must be sorted by descending value.
+///
+/// The first half of the sample is the largest candidates. The remaining candidates are
+/// split into equally sized strata, each contributing its first candidate, so every part
+/// of the value range is represented in proportion to its size. The sample keeps the order
+/// of `sorted`.
+pub fn stratified_sample<T>(sorted: Vec<T>, size: usize) -> Vec<T> {
+    if sorted.len() <= size {
+        return sorted;
+    }
+    if size == 0 {
+        return Vec::new();
+    }
+    let keep = size / 2;
+    let strata = size - keep;
+    let rest = sorted.len() - keep;
+
+    let mut next = keep;
+    let mut stratum = 0;
+    sorted
+        .into_iter()
+        .enumerate()
+        .filter(|&(i, _)| {
+            if i < keep {
+                return true;
+            }
+            if i != next {
+                return false;
+            }
+            stratum += 1;
+            next = keep + stratum * rest / strata;
+            true
+        })
+        .map(|(_, item)| item)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_inputs_are_kept() {
+        assert_eq!(stratified_sample(vec![3, 2, 1], 3), vec![3, 2, 1]);
+        assert_eq!(stratified_sample(Vec::<u32>::new(), 0), vec![]);
+    }
+
+    #[test]
+    fn largest_are_kept_and_rest_is_spread() {
+        let sorted: Vec<u32> = (0..100).rev().collect();
+        let sample = stratified_sample(sorted, 10);
+
+        assert_eq!(sample, vec![99, 98, 97, 96, 95, 94, 75, 56, 37, 18]);
+    }
+
+    #[test]
+    fn size_is_respected() {
+        for len in 0..50 {
+            for size in 0..len {
+                let sorted: Vec<usize> = (0..len).rev().collect();
+                assert_eq!(stratified_sample(sorted, size).len(), size);
+            }
+        }
+    }
+}
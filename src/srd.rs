@@ -0,0 +1,294 @@
+//! Single random draw.
+//!
+//! Bitcoin Core's last resort: candidates are drawn in random order until they cover the
+//! target plus a comfortable change. The result is rarely optimal, but it is hard to
+//! fingerprint and always finds a selection when one exists within the weight limit.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use bitcoin::{Amount, FeeRate, Weight};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::candidate::eligible_candidates;
+use crate::order::sort_candidates;
+use crate::target::check_target;
+use crate::{SelectionError, SelectionReport, WeightedUtxo};
+
+/// The least change a single random draw leaves, Bitcoin Core's `CHANGE_LOWER` of
+/// 50 000 sats.
+pub const CHANGE_LOWER: Amount = Amount::from_sat(50_000);
+
+/// The most change [`generate_change_target`] asks for, Bitcoin Core's `CHANGE_UPPER` of
+/// 1 000 000 sats.
+pub const CHANGE_UPPER: Amount = Amount::from_sat(1_000_000);
+
+/// Draws a change target for paying `payment`, like Bitcoin Core's `GenerateChangeTarget`.
+///
+/// Change of a constant value would give away which output is the change, so the target
+/// is drawn uniformly from `rng` between [`CHANGE_LOWER`] and the lower of twice
+/// `payment` and [`CHANGE_UPPER`]. Payments of up to half of [`CHANGE_LOWER`] get exactly
+/// [`CHANGE_LOWER`]. `change_fee`, the fee for the change output, is added.
+///
+/// Returns `None` on overflow.
+pub fn generate_change_target<R: Rng + ?Sized>(
+    payment: Amount,
+    change_fee: Amount,
+    rng: &mut R,
+) -> Option<Amount> {
+    let change = if payment.to_sat() <= CHANGE_LOWER.to_sat() / 2 {
+        CHANGE_LOWER
+    } else {
+        let upper = payment
+            .to_sat()
+            .saturating_mul(2)
+            .min(CHANGE_UPPER.to_sat());
+        CHANGE_LOWER + Amount::from_sat(rng.gen_range(0..upper - CHANGE_LOWER.to_sat()))
+    };
+    change.checked_add(change_fee)
+}
+
+/// Draws candidates at random until they cover `target` plus `change_target`, like
+/// Bitcoin Core's `SelectCoinsSRD`.
+///
+/// Bitcoin Core's change target is [`CHANGE_LOWER`] plus the fee for the change output,
+/// [`generate_change_target`] draws a randomized one.
+///
+/// Effective values at `fee_rate` are used and candidates whose effective value isn't
+/// positive are skipped. Whenever the drawn inputs weigh more than `max_weight` or number
+/// more than `max_input_count`, the ones with the lowest effective values are dropped
+/// until they fit again.
+///
+/// The selection is in canonical order, its excess over `target` is the change, and the
+/// report counts no iterations and records the change target. Returns
+/// [`SelectionError::MaxWeightExceeded`] if the
+/// weight limit prevented covering the target, or else
+/// [`SelectionError::MaxInputCountExceeded`] if the input count limit did.
+pub fn select_coins_srd<'a, U, R>(
+    target: Amount,
+    change_target: Amount,
+    max_weight: Weight,
+    max_input_count: usize,
+    fee_rate: FeeRate,
+    pool: &'a [U],
+    rng: &mut R,
+) -> Result<SelectionReport<'a, U>, SelectionError>
+where
+    U: WeightedUtxo,
+    R: Rng + ?Sized,
+{
+    check_target(target)?;
+    let required = target
+        .checked_add(change_target)
+        .ok_or(SelectionError::Overflow)?;
+
+    let mut candidates = eligible_candidates(pool, fee_rate, |_| {});
+    candidates.shuffle(rng);
+
+    // The drawn candidates by position in `candidates`, lowest effective value on top.
+    let mut drawn = BinaryHeap::new();
+    let mut value = Amount::ZERO;
+    let mut weight = Weight::ZERO;
+    let mut max_weight_exceeded = false;
+    let mut max_input_count_exceeded = false;
+    for (i, candidate) in candidates.iter().enumerate() {
+        drawn.push(Reverse((candidate.effective_value, i)));
+        value = value
+            .checked_add(candidate.effective_value)
+            .ok_or(SelectionError::Overflow)?;
+        weight = weight
+            .checked_add(candidate.weight)
+            .ok_or(SelectionError::Overflow)?;
+
+        while weight > max_weight || drawn.len() > max_input_count {
+            if weight > max_weight {
+                max_weight_exceeded = true;
+            } else {
+                max_input_count_exceeded = true;
+            }
+            let Some(Reverse((_, lowest))) = drawn.pop() else {
+                break;
+            };
+            value -= candidates[lowest].effective_value;
+            weight -= candidates[lowest].weight;
+        }
+
+        if value >= required {
+            let mut selection: Vec<_> = drawn
+                .into_iter()
+                .map(|Reverse((_, i))| candidates[i])
+                .collect();
+            sort_candidates(&mut selection);
+            let mut report = SelectionReport::new(selection, target, 0)?;
+            report.change_target = Some(change_target);
+            return Ok(report);
+        }
+    }
+
+    if max_weight_exceeded {
+        Err(SelectionError::MaxWeightExceeded { max_weight })
+    } else if max_input_count_exceeded {
+        Err(SelectionError::MaxInputCountExceeded { max_input_count })
+    } else {
+        Err(SelectionError::InsufficientFunds {
+            available: value,
+            required,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::build_pool;
+
+    const MAX_WEIGHT: Weight = Weight::from_wu(400_000);
+
+    fn select(
+        target: u64,
+        max_weight: Weight,
+        fee_rate: FeeRate,
+        pool: &[(u64, u64)],
+    ) -> Result<Vec<usize>, SelectionError> {
+        let pool = build_pool(pool);
+        let mut rng = StdRng::seed_from_u64(42);
+        select_coins_srd(
+            Amount::from_sat(target),
+            CHANGE_LOWER + Amount::from_sat(1_000),
+            max_weight,
+            usize::MAX,
+            fee_rate,
+            &pool,
+            &mut rng,
+        )
+        .map(|report| {
+            let mut indices: Vec<usize> = report.selection.iter().map(|c| c.index).collect();
+            indices.sort();
+            indices
+        })
+    }
+
+    #[test]
+    fn covers_target_and_change() {
+        let pool = build_pool(&[(30_000, 272); 10]);
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let report = select_coins_srd(
+                Amount::from_sat(40_000),
+                CHANGE_LOWER + Amount::from_sat(1_000),
+                MAX_WEIGHT,
+                usize::MAX,
+                fee_rate,
+                &pool,
+                &mut rng,
+            )
+            .unwrap();
+            // 28 920 per input, four are needed for 91 000.
+            assert_eq!(report.selection.len(), 4);
+            assert!(report.excess >= CHANGE_LOWER + Amount::from_sat(1_000));
+        }
+    }
+
+    #[test]
+    fn change_targets() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let change_fee = Amount::from_sat(1_000);
+        let mut generate = |payment| generate_change_target(payment, change_fee, &mut rng);
+
+        assert_eq!(
+            generate(Amount::from_sat(25_000)),
+            Some(CHANGE_LOWER + change_fee)
+        );
+        for _ in 0..100 {
+            let change = generate(Amount::from_sat(40_000)).unwrap() - change_fee;
+            assert!(CHANGE_LOWER <= change && change < Amount::from_sat(80_000));
+            let change = generate(Amount::from_sat(10_000_000)).unwrap() - change_fee;
+            assert!(CHANGE_LOWER <= change && change < CHANGE_UPPER);
+        }
+        let change_target = generate(Amount::from_sat(400_000)).unwrap();
+        assert_eq!(
+            generate_change_target(Amount::MAX, Amount::MAX, &mut rng),
+            None
+        );
+
+        let pool = build_pool(&[(300_000, 272); 10]);
+        let report = select_coins_srd(
+            Amount::from_sat(400_000),
+            change_target,
+            MAX_WEIGHT,
+            usize::MAX,
+            FeeRate::ZERO,
+            &pool,
+            &mut rng,
+        )
+        .unwrap();
+        assert_eq!(report.change_target, Some(change_target));
+        assert!(report.excess >= change_target);
+    }
+
+    #[test]
+    fn negative_effective_values_are_skipped() {
+        // The second candidate is worth less than the 1 080 sats it costs to spend.
+        let pool = [(100_000, 272), (1_000, 272)];
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        assert_eq!(select(40_000, MAX_WEIGHT, fee_rate, &pool), Ok(vec![0]));
+        assert_eq!(
+            select(50_000, MAX_WEIGHT, fee_rate, &pool),
+            Err(SelectionError::InsufficientFunds {
+                available: Amount::from_sat(98_920),
+                required: Amount::from_sat(101_000),
+            })
+        );
+    }
+
+    #[test]
+    fn lowest_values_make_room() {
+        // Only two inputs fit, so the small ones are dropped for the large one.
+        let pool = [(20_000, 112), (20_000, 112), (20_000, 112), (80_000, 112)];
+        assert_eq!(
+            select(40_000, Weight::from_wu(544), FeeRate::ZERO, &pool).map(|s| s.len()),
+            Ok(2)
+        );
+        assert_eq!(
+            select(60_000, Weight::from_wu(544), FeeRate::ZERO, &pool),
+            Err(SelectionError::MaxWeightExceeded {
+                max_weight: Weight::from_wu(544)
+            })
+        );
+    }
+
+    #[test]
+    fn max_input_count() {
+        let pool = build_pool(&[
+            (20_000, 0),
+            (20_000, 0),
+            (20_000, 0),
+            (80_000, 0),
+            (20_000, 0),
+        ]);
+        let select = |max_input_count| {
+            let mut rng = StdRng::seed_from_u64(42);
+            select_coins_srd(
+                Amount::from_sat(40_000),
+                CHANGE_LOWER + Amount::from_sat(1_000),
+                MAX_WEIGHT,
+                max_input_count,
+                FeeRate::ZERO,
+                &pool,
+                &mut rng,
+            )
+            .map(|report| report.selection.len())
+        };
+        // The large candidate is kept when small ones are dropped.
+        assert_eq!(select(2), Ok(2));
+        assert_eq!(
+            select(1),
+            Err(SelectionError::MaxInputCountExceeded { max_input_count: 1 })
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Standardness checks for a finished selection.
+//!
+//! A selection can satisfy its target and still produce a transaction that nodes refuse
+//! to relay, for example because it is too heavy or pays a dust output. Running
+//! [`check_standardness`] on every result before building the transaction turns those
+//! into precise errors instead of broadcast failures. [`CoinSelector::standardness`] runs
+//! them on the selections it returns.
+//!
+//! [`CoinSelector::standardness`]: crate::CoinSelector::standardness
+
+use std::fmt;
+
+use bitcoin::{Amount, TxOut, Weight};
+
+use crate::defaults;
+use crate::dust::DustCalculator;
+use crate::{predict_transaction_weight, WeightedUtxo};
+
+/// Limits a selection must respect to be relayable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StandardnessLimits {
+    /// Maximum number of inputs. Defaults to 252, the most inputs whose count fits in a
+    /// single byte.
+    pub max_inputs: usize,
+    /// Maximum weight of the whole transaction. Defaults to 400 000 wu, Bitcoin Core's
+    /// `MAX_STANDARD_TX_WEIGHT`.
+    pub max_weight: Weight,
+}
+
+impl Default for StandardnessLimits {
+    fn default() -> Self {
+        StandardnessLimits {
+            max_inputs: defaults::MAX_SINGLE_BYTE_INPUTS,
+            max_weight: defaults::MAX_STANDARD_TX_WEIGHT,
+        }
+    }
+}
+
+/// Reason a selection would not produce a standard transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StandardnessError {
+    /// The selection is empty.
+    NoInputs,
+    /// The selection has more inputs than allowed.
+    TooManyInputs {
+        /// Number of selected inputs.
+        count: usize,
+        /// The configured maximum.
+        max: usize,
+    },
+    /// The predicted transaction weight exceeds the maximum, or overflows.
+    ExcessiveWeight {
+        /// The predicted weight, `None` on overflow.
+        weight: Option<Weight>,
+        /// The configured maximum.
+        max: Weight,
+    },
+    /// An output is below its dust threshold.
+    DustOutput {
+        /// Index of the output.
+        index: usize,
+        /// Value of the output.
+        value: Amount,
+        /// Dust threshold of the output's script.
+        threshold: Amount,
+    },
+}
+
+impl fmt::Display for StandardnessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StandardnessError::NoInputs => write!(f, "selection has no inputs"),
+            StandardnessError::TooManyInputs { count, max } => write!(
+                f,
+                "selection has {} inputs, at most {} are allowed",
+                count, max
+            ),
+            StandardnessError::ExcessiveWeight {
+                weight: Some(weight),
+                max,
+            } => write!(
+                f,
+                "transaction weight {} exceeds the maximum of {}",
+                weight, max
+            ),
+            StandardnessError::ExcessiveWeight { weight: None, .. } => {
+                write!(f, "transaction weight overflows")
+            }
+            StandardnessError::DustOutput {
+                index,
+                value,
+                threshold,
+            } => write!(
+                f,
+                "output {} of {} is below the dust threshold of {}",
+                index, value, threshold
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StandardnessError {}
+
+/// Checks that spending `selection` to `outputs` yields a standard transaction.
+///
+/// `outputs` must include the change output, if any. The transaction weight is predicted
+/// with [`predict_transaction_weight`], which is returned on success.
+pub fn check_standardness<'a, U, I, D>(
+    selection: I,
+    outputs: &[TxOut],
+    limits: &StandardnessLimits,
+    dust: &D,
+) -> Result<Weight, StandardnessError>
+where
+    U: WeightedUtxo + 'a,
+    I: IntoIterator<Item = &'a U>,
+    I::IntoIter: Clone,
+    D: DustCalculator + ?Sized,
+{
+    let selection = selection.into_iter();
+
+    let count = selection.clone().count();
+    if count == 0 {
+        return Err(StandardnessError::NoInputs);
+    }
+    if count > limits.max_inputs {
+        return Err(StandardnessError::TooManyInputs {
+            count,
+            max: limits.max_inputs,
+        });
+    }
+
+    let weight = match predict_transaction_weight(selection, outputs, false) {
+        Some(weight) if weight <= limits.max_weight => weight,
+        weight => {
+            return Err(StandardnessError::ExcessiveWeight {
+                weight,
+                max: limits.max_weight,
+            })
+        }
+    };
+
+    for (index, output) in outputs.iter().enumerate() {
+        let threshold = dust.dust_threshold(&output.script_pubkey);
+        if output.value < threshold {
+            return Err(StandardnessError::DustOutput {
+                index,
+                value: output.value,
+                threshold,
+            });
+        }
+    }
+
+    Ok(weight)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{ScriptBuf, WPubkeyHash};
+
+    use super::*;
+    use crate::dust::CoreDust;
+    use crate::tests::build_pool;
+
+    fn p2wpkh_output(value: u64) -> TxOut {
+        TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+        }
+    }
+
+    #[test]
+    fn standard_selection() {
+        let pool = build_pool(&[(100_000, 112), (50_000, 112)]);
+        let outputs = [p2wpkh_output(120_000), p2wpkh_output(294)];
+
+        let weight = check_standardness(&pool, &outputs, &Default::default(), &CoreDust::default());
+        assert_eq!(
+            weight,
+            Ok(predict_transaction_weight(&pool, &outputs, false).unwrap())
+        );
+    }
+
+    #[test]
+    fn empty_selection() {
+        let pool = build_pool(&[]);
+        let outputs = [p2wpkh_output(1_000)];
+        assert_eq!(
+            check_standardness(&pool, &outputs, &Default::default(), &CoreDust::default()),
+            Err(StandardnessError::NoInputs)
+        );
+    }
+
+    #[test]
+    fn too_many_inputs() {
+        let pool = build_pool(&[(1_000, 112); 253]);
+        let outputs = [p2wpkh_output(1_000)];
+        assert_eq!(
+            check_standardness(&pool, &outputs, &Default::default(), &CoreDust::default()),
+            Err(StandardnessError::TooManyInputs {
+                count: 253,
+                max: 252
+            })
+        );
+    }
+
+    #[test]
+    fn excessive_weight() {
+        let pool = build_pool(&[(1_000, 112); 10]);
+        let outputs = [p2wpkh_output(1_000)];
+        let limits = StandardnessLimits {
+            max_weight: Weight::from_wu(1_000),
+            ..Default::default()
+        };
+
+        let weight = predict_transaction_weight(&pool, &outputs, false);
+        assert_eq!(
+            check_standardness(&pool, &outputs, &limits, &CoreDust::default()),
+            Err(StandardnessError::ExcessiveWeight {
+                weight,
+                max: Weight::from_wu(1_000)
+            })
+        );
+
+        let pool = build_pool(&[(1_000, u64::MAX)]);
+        assert_eq!(
+            check_standardness(&pool, &outputs, &limits, &CoreDust::default()),
+            Err(StandardnessError::ExcessiveWeight {
+                weight: None,
+                max: Weight::from_wu(1_000)
+            })
+        );
+    }
+
+    #[test]
+    fn dust_output() {
+        let pool = build_pool(&[(100_000, 112)]);
+        let outputs = [p2wpkh_output(50_000), p2wpkh_output(293)];
+        assert_eq!(
+            check_standardness(&pool, &outputs, &Default::default(), &CoreDust::default()),
+            Err(StandardnessError::DustOutput {
+                index: 1,
+                value: Amount::from_sat(293),
+                threshold: Amount::from_sat(294),
+            })
+        );
+    }
+}
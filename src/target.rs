@@ -0,0 +1,244 @@
+//! What a selection target includes.
+//!
+//! The selection algorithms compare the sum of the effective values of the selected
+//! inputs against a target. That target must already contain the fee for every part of
+//! the transaction other than the inputs, since input fees are accounted for by the
+//! effective values. [`TargetKind`] makes explicit whether the caller did that
+//! computation or wants it done here, and [`target_from_outputs`] does it for a list of
+//! recipient outputs.
+
+use core::convert::TryFrom;
+use std::fmt;
+
+use bitcoin::{Amount, FeeRate, SignedAmount, TxOut, Weight};
+
+use crate::utxo::Utxo;
+use crate::weight::predict_transaction_weight;
+
+/// The target of a selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    /// The exact amount the effective values of the selected inputs must cover.
+    ///
+    /// The caller has already added the fee for the transaction header and outputs.
+    Effective(Amount),
+    /// The recipient amounts plus the fee for the rest of the transaction.
+    RecipientsPlusOverhead {
+        /// Sum of the values of the recipient outputs.
+        outputs: Amount,
+        /// Weight of the transaction without its inputs: version, locktime, input and
+        /// output counts, segwit marker and flag if any, and the serialized outputs.
+        base_weight: Weight,
+    },
+}
+
+impl TargetKind {
+    /// The target for paying `outputs`, a change output not included.
+    ///
+    /// The base weight is that of a segwit transaction with up to
+    /// [`MAX_SINGLE_BYTE_INPUTS`](crate::defaults::MAX_SINGLE_BYTE_INPUTS) inputs, whose
+    /// input count is encoded in a single byte. The output count and script lengths are
+    /// encoded with however many bytes they need.
+    ///
+    /// Returns `None` on overflow.
+    pub fn from_outputs(outputs: &[TxOut]) -> Option<Self> {
+        let mut total = Amount::ZERO;
+        for output in outputs {
+            total = total.checked_add(output.value)?;
+        }
+        Some(TargetKind::RecipientsPlusOverhead {
+            outputs: total,
+            base_weight: predict_transaction_weight::<Utxo, _>(&[], outputs, false)?,
+        })
+    }
+
+    /// Returns the amount the effective values of the selected inputs must cover when
+    /// paying `fee_rate`.
+    ///
+    /// Returns `None` on overflow.
+    pub fn effective_target(&self, fee_rate: FeeRate) -> Option<Amount> {
+        match *self {
+            TargetKind::Effective(target) => Some(target),
+            TargetKind::RecipientsPlusOverhead {
+                outputs,
+                base_weight,
+            } => outputs.checked_add(fee_rate.fee_wu(base_weight)?),
+        }
+    }
+}
+
+/// Returns the amount the effective values of the selected inputs must cover to pay
+/// `outputs` at `fee_rate`, see [`TargetKind::from_outputs`].
+///
+/// Returns `None` on overflow.
+pub fn target_from_outputs(outputs: &[TxOut], fee_rate: FeeRate) -> Option<Amount> {
+    TargetKind::from_outputs(outputs)?.effective_target(fee_rate)
+}
+
+/// Converts a signed target into an effective target.
+///
+/// Rebalancing flows often derive the target from signed arithmetic. Rather than clamping
+/// before calling in, the signed amount can be converted here, failing for values that
+/// can't be the target of a selection.
+impl TryFrom<SignedAmount> for TargetKind {
+    type Error = InvalidTargetError;
+
+    fn try_from(target: SignedAmount) -> Result<Self, Self::Error> {
+        if target == SignedAmount::ZERO {
+            return Err(InvalidTargetError::Zero);
+        }
+        target
+            .to_unsigned()
+            .map(TargetKind::Effective)
+            .map_err(|_| InvalidTargetError::Negative(target))
+    }
+}
+
+/// A target that no selection can meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InvalidTargetError {
+    /// The target is zero.
+    Zero,
+    /// The target is negative.
+    Negative(
+        #[cfg_attr(feature = "serde", serde(with = "bitcoin::amount::serde::as_sat"))] SignedAmount,
+    ),
+}
+
+impl fmt::Display for InvalidTargetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidTargetError::Zero => write!(f, "target is zero"),
+            InvalidTargetError::Negative(target) => write!(f, "target {} is negative", target),
+        }
+    }
+}
+
+impl std::error::Error for InvalidTargetError {}
+
+/// Rejects a target that no selection can meet, see
+/// [`TargetKind::try_from`](TargetKind#impl-TryFrom<SignedAmount>-for-TargetKind).
+pub(crate) fn check_target(target: Amount) -> Result<(), InvalidTargetError> {
+    // Targets beyond the range of a signed amount are valid, just not coverable.
+    let target = target.to_signed().unwrap_or(SignedAmount::MAX);
+    TargetKind::try_from(target).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_u32(10);
+
+    #[test]
+    fn effective_target_is_used_as_is() {
+        let target = TargetKind::Effective(Amount::from_sat(50_000));
+        assert_eq!(
+            target.effective_target(FEE_RATE),
+            Some(Amount::from_sat(50_000))
+        );
+    }
+
+    #[test]
+    fn overhead_is_charged_at_fee_rate() {
+        // Header of a segwit transaction with one P2WPKH output: 10.5 + 31 vB.
+        let target = TargetKind::RecipientsPlusOverhead {
+            outputs: Amount::from_sat(50_000),
+            base_weight: Weight::from_wu(42 + 124),
+        };
+        assert_eq!(
+            target.effective_target(FEE_RATE),
+            Some(Amount::from_sat(50_415))
+        );
+        assert_eq!(
+            target.effective_target(FeeRate::ZERO),
+            Some(Amount::from_sat(50_000))
+        );
+    }
+
+    #[test]
+    fn target_from_recipient_outputs() {
+        use bitcoin::hashes::Hash;
+        use bitcoin::{ScriptBuf, WPubkeyHash};
+
+        let output = TxOut {
+            value: Amount::from_sat(50_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+        };
+        // Version, locktime, input and output counts: 10 vB, marker and flag: 0.5 vB,
+        // P2WPKH output: 31 vB.
+        assert_eq!(
+            TargetKind::from_outputs(core::slice::from_ref(&output)),
+            Some(TargetKind::RecipientsPlusOverhead {
+                outputs: Amount::from_sat(50_000),
+                base_weight: Weight::from_wu(42 + 124),
+            })
+        );
+        assert_eq!(
+            target_from_outputs(core::slice::from_ref(&output), FEE_RATE),
+            Some(Amount::from_sat(50_415))
+        );
+
+        // From 253 outputs on, the output count takes three bytes instead of one.
+        let base_weight = |count| match TargetKind::from_outputs(&vec![output.clone(); count]) {
+            Some(TargetKind::RecipientsPlusOverhead { base_weight, .. }) => base_weight,
+            _ => Weight::ZERO,
+        };
+        assert_eq!(base_weight(252) - base_weight(251), Weight::from_wu(124));
+        assert_eq!(
+            base_weight(253) - base_weight(252),
+            Weight::from_wu(124 + 8)
+        );
+
+        // Scripts of 253 bytes and more have a three byte length prefix.
+        let long = |len| TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::from_bytes(vec![0; len]),
+        };
+        let weight = |len| match TargetKind::from_outputs(&[long(len)]) {
+            Some(TargetKind::RecipientsPlusOverhead { base_weight, .. }) => base_weight,
+            _ => Weight::ZERO,
+        };
+        assert_eq!(weight(253) - weight(252), Weight::from_wu(4 + 8));
+
+        let max = TxOut {
+            value: Amount::MAX,
+            ..output
+        };
+        assert_eq!(TargetKind::from_outputs(&[max.clone(), max]), None);
+        assert_eq!(
+            target_from_outputs(&[], FEE_RATE),
+            Some(Amount::from_sat(105))
+        );
+    }
+
+    #[test]
+    fn overhead_overflow() {
+        let target = TargetKind::RecipientsPlusOverhead {
+            outputs: Amount::MAX,
+            base_weight: Weight::from_wu(1),
+        };
+        assert_eq!(target.effective_target(FEE_RATE), None);
+    }
+
+    #[test]
+    fn signed_target() {
+        let target = TargetKind::try_from(SignedAmount::from_sat(50_000));
+        assert_eq!(target, Ok(TargetKind::Effective(Amount::from_sat(50_000))));
+    }
+
+    #[test]
+    fn non_positive_signed_target() {
+        assert_eq!(
+            TargetKind::try_from(SignedAmount::ZERO),
+            Err(InvalidTargetError::Zero)
+        );
+
+        let negative = SignedAmount::from_sat(-1);
+        assert_eq!(
+            TargetKind::try_from(negative),
+            Err(InvalidTargetError::Negative(negative))
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! A ready-made candidate type.
+//!
+//! Wallets usually have their own UTXO type and implement [`WeightedUtxo`] for it. When
+//! all that is at hand is the output itself, [`Utxo`] can be used instead, with the
+//! satisfaction weight inferred from the script for common script types.
+
+use bitcoin::{Amount, OutPoint, Script, TxOut, Weight};
+
+use crate::weight::{infer_satisfaction_weight, UnsupportedScriptError};
+use crate::{UtxoMetadata, WeightedUtxo};
+
+/// An unspent output and the weight needed to spend it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Utxo {
+    /// The outpoint of the output.
+    pub outpoint: OutPoint,
+    /// The output.
+    pub txout: TxOut,
+    /// The weight of the `scriptSig` and witness needed to spend the output.
+    pub satisfaction_weight: Weight,
+}
+
+impl Utxo {
+    /// Creates a candidate spent with `satisfaction_weight`.
+    pub fn new(outpoint: OutPoint, txout: TxOut, satisfaction_weight: Weight) -> Self {
+        Utxo {
+            outpoint,
+            txout,
+            satisfaction_weight,
+        }
+    }
+
+    /// Creates a candidate whose satisfaction weight is inferred from its script.
+    ///
+    /// See [`infer_satisfaction_weight`] for the supported script types. Others must be
+    /// created with [`Utxo::new`].
+    pub fn from_txout(outpoint: OutPoint, txout: TxOut) -> Result<Self, UnsupportedScriptError> {
+        let satisfaction_weight = infer_satisfaction_weight(&txout.script_pubkey)?;
+        Ok(Utxo::new(outpoint, txout, satisfaction_weight))
+    }
+}
+
+impl WeightedUtxo for Utxo {
+    fn satisfaction_weight(&self) -> Weight {
+        self.satisfaction_weight
+    }
+
+    fn value(&self) -> Amount {
+        self.txout.value
+    }
+}
+
+impl UtxoMetadata for Utxo {
+    fn outpoint(&self) -> OutPoint {
+        self.outpoint
+    }
+
+    fn script_pubkey(&self) -> &Script {
+        &self.txout.script_pubkey
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{ScriptBuf, WPubkeyHash, WScriptHash};
+
+    use super::*;
+    use crate::weight;
+
+    #[test]
+    fn weight_is_inferred() {
+        let txout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+        };
+        let utxo = Utxo::from_txout(OutPoint::null(), txout).unwrap();
+        assert_eq!(utxo.satisfaction_weight(), weight::p2wpkh());
+        assert_eq!(utxo.value(), Amount::from_sat(10_000));
+    }
+
+    #[test]
+    fn unsupported_script_needs_explicit_weight() {
+        let txout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wsh(&WScriptHash::all_zeros()),
+        };
+        assert_eq!(
+            Utxo::from_txout(OutPoint::null(), txout.clone()),
+            Err(UnsupportedScriptError)
+        );
+
+        let utxo = Utxo::new(OutPoint::null(), txout, weight::p2wsh_multisig(2, 3));
+        assert_eq!(utxo.satisfaction_weight(), weight::p2wsh_multisig(2, 3));
+    }
+}
@@ -0,0 +1,549 @@
+//! Satisfaction weight presets for common script types.
+//!
+//! The satisfaction weight of an input is the weight of its `scriptSig` (including the
+//! length prefix) plus the weight of its witness (including the element count and the
+//! length prefix of every element). It does not include the outpoint and sequence, which
+//! are the same for every input.
+//!
+//! Every preset is a `const fn` so that tables of weights can be computed at compile time:
+//!
+//! ```
+//! use bitcoin::Weight;
+//! use rust_bitcoin_coin_selection::weight;
+//!
+//! const VAULT_INPUTS: [Weight; 2] = [weight::p2wsh_multisig(2, 3), weight::p2tr_key_spend()];
+//! assert_eq!(VAULT_INPUTS[1], Weight::from_wu(66 + 4));
+//! ```
+//!
+//! Signatures are assumed to be of maximum size (72 byte DER signatures including the
+//! sighash flag) and public keys to be compressed unless stated otherwise.
+//!
+//! [`predict_transaction_weight`] adds the weight of the rest of the transaction to the
+//! weights of a selection.
+//!
+//! Since real signatures occasionally exceed these estimates, [`pad`] reserves a margin on
+//! top of the satisfaction weight of every candidate of a pool.
+
+use core::iter;
+use std::fmt;
+
+use bitcoin::transaction::predict_weight;
+use bitcoin::{Amount, OutPoint, Script, SignedAmount, TxOut, Weight};
+
+use crate::{UtxoChainInfo, UtxoMetadata, WeightedUtxo};
+
+/// Maximum size of a DER encoded ECDSA signature including the sighash flag.
+const ECDSA_SIGNATURE_SIZE: u64 = 72;
+
+/// Size of a compressed public key.
+const COMPRESSED_PUBKEY_SIZE: u64 = 33;
+
+/// Size of an uncompressed public key.
+const UNCOMPRESSED_PUBKEY_SIZE: u64 = 65;
+
+/// Size of a schnorr signature using `SIGHASH_DEFAULT`.
+const SCHNORR_SIGNATURE_SIZE: u64 = 64;
+
+/// Weight of the segwit marker and flag.
+const SEGWIT_MARKER_WEIGHT: Weight = Weight::from_wu(2);
+
+/// Length of the change output script assumed by [`predict_transaction_weight`] (P2WPKH).
+pub const CHANGE_SCRIPT_LEN: usize = 22;
+
+/// Maximum number of public keys in a standard `OP_CHECKMULTISIG` script.
+const MAX_MULTISIG_KEYS: u64 = 20;
+
+/// Maximum number of public keys in a P2SH multisig redeem script (520 byte push limit).
+const MAX_P2SH_MULTISIG_KEYS: u64 = 15;
+
+/// Size of the compact size (varint) encoding of `n`.
+const fn compact_size_len(n: u64) -> u64 {
+    if n < 0xFD {
+        1
+    } else if n <= 0xFFFF {
+        3
+    } else if n <= 0xFFFF_FFFF {
+        5
+    } else {
+        9
+    }
+}
+
+/// Size of the opcode(s) needed to push `len` bytes onto the stack.
+const fn push_opcode_len(len: u64) -> u64 {
+    if len < 0x4C {
+        1
+    } else if len <= 0xFF {
+        2
+    } else if len <= 0xFFFF {
+        3
+    } else {
+        5
+    }
+}
+
+/// Weight of a `scriptSig` of `len` bytes, including its length prefix.
+const fn script_sig_weight(len: u64) -> u64 {
+    (compact_size_len(len) + len) * 4
+}
+
+/// Weight of a witness stack, given the number of elements and their total size.
+///
+/// `prefixes` is the combined size of the length prefixes of every element.
+const fn witness_weight(count: u64, prefixes: u64, data: u64) -> u64 {
+    compact_size_len(count) + prefixes + data
+}
+
+/// Size of a `m`-of-`n` `OP_CHECKMULTISIG` script using compressed keys.
+const fn multisig_script_len(n: u64) -> u64 {
+    // OP_m <push pubkey>*n OP_n OP_CHECKMULTISIG
+    1 + n * (1 + COMPRESSED_PUBKEY_SIZE) + 1 + 1
+}
+
+/// Size of the pushes for `m` signatures preceded by the `OP_CHECKMULTISIG` dummy element.
+const fn multisig_script_sig_len(m: u64) -> u64 {
+    1 + m * (1 + ECDSA_SIGNATURE_SIZE)
+}
+
+const fn check_multisig(m: u64, n: u64, max_keys: u64) {
+    assert!(m >= 1, "multisig requires at least one signature");
+    assert!(m <= n, "multisig requires m <= n");
+    assert!(
+        n <= max_keys,
+        "too many public keys for a standard multisig script"
+    );
+}
+
+/// Satisfaction weight of a P2PKH input spent with a compressed public key.
+pub const fn p2pkh_compressed() -> Weight {
+    let script_sig = 1 + ECDSA_SIGNATURE_SIZE + 1 + COMPRESSED_PUBKEY_SIZE;
+    Weight::from_wu(script_sig_weight(script_sig))
+}
+
+/// Satisfaction weight of a P2PKH input spent with an uncompressed public key.
+pub const fn p2pkh_uncompressed() -> Weight {
+    let script_sig = 1 + ECDSA_SIGNATURE_SIZE + 1 + UNCOMPRESSED_PUBKEY_SIZE;
+    Weight::from_wu(script_sig_weight(script_sig))
+}
+
+/// Satisfaction weight of a P2WPKH input.
+pub const fn p2wpkh() -> Weight {
+    let witness = witness_weight(2, 2, ECDSA_SIGNATURE_SIZE + COMPRESSED_PUBKEY_SIZE);
+    Weight::from_wu(script_sig_weight(0) + witness)
+}
+
+/// Satisfaction weight of a P2WPKH input nested in P2SH.
+pub const fn p2sh_p2wpkh() -> Weight {
+    // The scriptSig pushes the 22 byte witness program `OP_0 <20 byte hash>`.
+    let witness = witness_weight(2, 2, ECDSA_SIGNATURE_SIZE + COMPRESSED_PUBKEY_SIZE);
+    Weight::from_wu(script_sig_weight(1 + 22) + witness)
+}
+
+/// Satisfaction weight of a P2TR key path spend using `SIGHASH_DEFAULT`.
+pub const fn p2tr_key_spend() -> Weight {
+    Weight::from_wu(script_sig_weight(0) + witness_weight(1, 1, SCHNORR_SIGNATURE_SIZE))
+}
+
+/// Satisfaction weight of a P2TR key path spend using an explicit sighash flag.
+pub const fn p2tr_key_spend_non_default_sighash() -> Weight {
+    Weight::from_wu(script_sig_weight(0) + witness_weight(1, 1, SCHNORR_SIGNATURE_SIZE + 1))
+}
+
+/// Satisfaction weight of a bare `m`-of-`n` multisig input.
+///
+/// # Panics
+///
+/// If `m` is zero, `m > n` or `n > 20`. When evaluated in a const context this is a
+/// compile time error.
+pub const fn p2ms(m: u64, n: u64) -> Weight {
+    check_multisig(m, n, MAX_MULTISIG_KEYS);
+    Weight::from_wu(script_sig_weight(multisig_script_sig_len(m)))
+}
+
+/// Satisfaction weight of a `m`-of-`n` multisig input wrapped in P2SH.
+///
+/// # Panics
+///
+/// If `m` is zero, `m > n` or `n > 15`. When evaluated in a const context this is a
+/// compile time error.
+pub const fn p2sh_multisig(m: u64, n: u64) -> Weight {
+    check_multisig(m, n, MAX_P2SH_MULTISIG_KEYS);
+    let redeem_script = multisig_script_len(n);
+    let script_sig = multisig_script_sig_len(m) + push_opcode_len(redeem_script) + redeem_script;
+    Weight::from_wu(script_sig_weight(script_sig))
+}
+
+/// Satisfaction weight of a `m`-of-`n` multisig input using P2WSH.
+///
+/// # Panics
+///
+/// If `m` is zero, `m > n` or `n > 20`. When evaluated in a const context this is a
+/// compile time error.
+pub const fn p2wsh_multisig(m: u64, n: u64) -> Weight {
+    check_multisig(m, n, MAX_MULTISIG_KEYS);
+    Weight::from_wu(script_sig_weight(0) + p2wsh_multisig_witness(m, n))
+}
+
+/// Satisfaction weight of a `m`-of-`n` multisig input using P2WSH nested in P2SH.
+///
+/// # Panics
+///
+/// If `m` is zero, `m > n` or `n > 20`. When evaluated in a const context this is a
+/// compile time error.
+pub const fn p2sh_p2wsh_multisig(m: u64, n: u64) -> Weight {
+    check_multisig(m, n, MAX_MULTISIG_KEYS);
+    // The scriptSig pushes the 34 byte witness program `OP_0 <32 byte hash>`.
+    Weight::from_wu(script_sig_weight(1 + 34) + p2wsh_multisig_witness(m, n))
+}
+
+/// Witness weight of a `m`-of-`n` P2WSH multisig spend.
+const fn p2wsh_multisig_witness(m: u64, n: u64) -> u64 {
+    let witness_script = multisig_script_len(n);
+    // Empty dummy element, m signatures and the witness script.
+    let count = 1 + m + 1;
+    let prefixes = 1 + m + compact_size_len(witness_script);
+    let data = m * ECDSA_SIGNATURE_SIZE + witness_script;
+    witness_weight(count, prefixes, data)
+}
+
+/// Infers the satisfaction weight of an output from its script.
+///
+/// Only script types whose spend is determined by the script are supported: P2PKH
+/// (assuming a compressed key), P2WPKH and P2TR key path spends. A script hash may hide
+/// any script and bare multisig needs the number of required signatures, so their
+/// satisfaction weight must be given explicitly.
+pub fn infer_satisfaction_weight(script_pubkey: &Script) -> Result<Weight, UnsupportedScriptError> {
+    if script_pubkey.is_p2pkh() {
+        Ok(p2pkh_compressed())
+    } else if script_pubkey.is_p2wpkh() {
+        Ok(p2wpkh())
+    } else if script_pubkey.is_p2tr() {
+        Ok(p2tr_key_spend())
+    } else {
+        Err(UnsupportedScriptError)
+    }
+}
+
+/// The satisfaction weight of a script can't be inferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedScriptError;
+
+impl fmt::Display for UnsupportedScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "script type requires an explicit satisfaction weight")
+    }
+}
+
+impl std::error::Error for UnsupportedScriptError {}
+
+/// Predicts the weight of a transaction spending `selection` and paying to `outputs`.
+///
+/// The header and outputs are weighed by [`bitcoin::transaction::predict_weight`] and
+/// every input by [`WeightedUtxo::weight`], so the figure is consistent with the weights
+/// used during selection. If `include_change` is set, a change output with a script of
+/// [`CHANGE_SCRIPT_LEN`] bytes is added.
+///
+/// A satisfaction weight doesn't tell whether an input has a witness, so the transaction
+/// is assumed to be segwit and the marker and flag are always counted. A legacy input in
+/// a segwit transaction additionally needs an empty witness, one weight unit which the
+/// presets in this module don't include.
+///
+/// Returns `None` on overflow.
+pub fn predict_transaction_weight<'a, U, I>(
+    selection: I,
+    outputs: &[TxOut],
+    include_change: bool,
+) -> Option<Weight>
+where
+    U: WeightedUtxo + 'a,
+    I: IntoIterator<Item = &'a U>,
+{
+    let (input_count, input_weight) = selection
+        .into_iter()
+        .try_fold((0, Weight::ZERO), |(count, total), utxo| {
+            Some((count + 1, total.checked_add(utxo.weight()?)?))
+        })?;
+
+    let change = if include_change {
+        Some(CHANGE_SCRIPT_LEN)
+    } else {
+        None
+    };
+    let script_lens = outputs
+        .iter()
+        .map(|txout| txout.script_pubkey.len())
+        .chain(change);
+
+    // Without inputs the input count is encoded in a single byte.
+    let skeleton = predict_weight(iter::empty(), script_lens);
+    let input_count_weight = Weight::from_non_witness_data_size(compact_size_len(input_count) - 1);
+
+    skeleton
+        .checked_add(input_count_weight)?
+        .checked_add(input_weight)?
+        .checked_add(SEGWIT_MARKER_WEIGHT)
+}
+
+/// Adds `margin` to the [weight margin](WeightedUtxo::weight_margin) of every candidate of
+/// `pool`.
+///
+/// The result has a candidate for each of `pool` at the same index and can be passed to
+/// any selector.
+pub fn pad<U: WeightedUtxo>(pool: &[U], margin: Weight) -> Vec<Padded<'_, U>> {
+    pool.iter().map(|utxo| Padded { utxo, margin }).collect()
+}
+
+/// A candidate with an additional weight margin, see [`pad`].
+#[derive(Debug)]
+pub struct Padded<'a, U> {
+    utxo: &'a U,
+    margin: Weight,
+}
+
+impl<U> Clone for Padded<'_, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<U> Copy for Padded<'_, U> {}
+
+impl<'a, U> Padded<'a, U> {
+    /// The candidate.
+    pub fn utxo(&self) -> &'a U {
+        self.utxo
+    }
+}
+
+impl<U: WeightedUtxo> WeightedUtxo for Padded<'_, U> {
+    fn satisfaction_weight(&self) -> Weight {
+        self.utxo.satisfaction_weight()
+    }
+
+    fn value(&self) -> Amount {
+        self.utxo.value()
+    }
+
+    /// The margin of the candidate plus the padding, [`Weight::MAX`] on overflow so that
+    /// the weight overflows too.
+    fn weight_margin(&self) -> Weight {
+        self.utxo
+            .weight_margin()
+            .checked_add(self.margin)
+            .unwrap_or(Weight::MAX)
+    }
+
+    fn fee_adjustment(&self) -> SignedAmount {
+        self.utxo.fee_adjustment()
+    }
+
+    fn ancestor_fee(&self) -> Amount {
+        self.utxo.ancestor_fee()
+    }
+
+    fn ancestor_weight(&self) -> Weight {
+        self.utxo.ancestor_weight()
+    }
+
+    fn is_spendable(&self) -> bool {
+        self.utxo.is_spendable()
+    }
+}
+
+impl<U: UtxoMetadata> UtxoMetadata for Padded<'_, U> {
+    fn outpoint(&self) -> OutPoint {
+        self.utxo.outpoint()
+    }
+
+    fn script_pubkey(&self) -> &Script {
+        self.utxo.script_pubkey()
+    }
+}
+
+impl<U: UtxoChainInfo> UtxoChainInfo for Padded<'_, U> {
+    fn confirmations(&self) -> u32 {
+        self.utxo.confirmations()
+    }
+
+    fn is_coinbase(&self) -> bool {
+        self.utxo.is_coinbase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_pool;
+    use bitcoin::hashes::Hash;
+    use bitcoin::key::{TweakedPublicKey, XOnlyPublicKey};
+    use bitcoin::transaction::InputWeightPrediction;
+    use bitcoin::{Amount, PubkeyHash, ScriptBuf, ScriptHash, WPubkeyHash, WScriptHash};
+
+    #[test]
+    fn inferred_weights() {
+        let wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        assert_eq!(infer_satisfaction_weight(&wpkh), Ok(p2wpkh()));
+
+        let pkh = ScriptBuf::new_p2pkh(&PubkeyHash::all_zeros());
+        assert_eq!(infer_satisfaction_weight(&pkh), Ok(p2pkh_compressed()));
+
+        let tr = ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(
+            XOnlyPublicKey::from_slice(&[1; 32]).unwrap(),
+        ));
+        assert_eq!(infer_satisfaction_weight(&tr), Ok(p2tr_key_spend()));
+
+        let wsh = ScriptBuf::new_p2wsh(&WScriptHash::all_zeros());
+        assert_eq!(infer_satisfaction_weight(&wsh), Err(UnsupportedScriptError));
+        let sh = ScriptBuf::new_p2sh(&ScriptHash::all_zeros());
+        assert_eq!(infer_satisfaction_weight(&sh), Err(UnsupportedScriptError));
+    }
+
+    #[test]
+    fn single_key_presets_match_rust_bitcoin() {
+        assert_eq!(
+            p2pkh_compressed(),
+            InputWeightPrediction::P2PKH_COMPRESSED_MAX.weight()
+        );
+        assert_eq!(
+            p2pkh_uncompressed(),
+            InputWeightPrediction::P2PKH_UNCOMPRESSED_MAX.weight()
+        );
+        assert_eq!(p2wpkh(), InputWeightPrediction::P2WPKH_MAX.weight());
+        assert_eq!(
+            p2tr_key_spend(),
+            InputWeightPrediction::P2TR_KEY_DEFAULT_SIGHASH.weight()
+        );
+        assert_eq!(
+            p2tr_key_spend_non_default_sighash(),
+            InputWeightPrediction::P2TR_KEY_NON_DEFAULT_SIGHASH.weight()
+        );
+    }
+
+    #[test]
+    fn multisig_presets_match_rust_bitcoin() {
+        let script = multisig_script_len(3) as usize;
+        let p2wsh = InputWeightPrediction::new(0, [0, 72, 72, script]);
+        assert_eq!(p2wsh_multisig(2, 3), p2wsh.weight());
+
+        let p2sh_p2wsh = InputWeightPrediction::new(35, [0, 72, 72, script]);
+        assert_eq!(p2sh_p2wsh_multisig(2, 3), p2sh_p2wsh.weight());
+
+        // OP_0 <sig> <sig> <push redeem script>
+        let p2sh = InputWeightPrediction::new(1 + 73 * 2 + 2 + script, [0usize; 0]);
+        assert_eq!(p2sh_multisig(2, 3), p2sh.weight());
+
+        let bare = InputWeightPrediction::new(1 + 73, [0usize; 0]);
+        assert_eq!(p2ms(1, 1), bare.weight());
+    }
+
+    #[test]
+    fn presets_are_const() {
+        const TABLE: [Weight; 3] = [p2wpkh(), p2sh_p2wpkh(), p2wsh_multisig(2, 2)];
+        assert_eq!(TABLE[0], Weight::from_wu(112));
+        assert_eq!(TABLE[1], Weight::from_wu(204));
+        assert!(TABLE[2] > TABLE[0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn multisig_m_greater_than_n() {
+        p2wsh_multisig(3, 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn p2sh_multisig_too_many_keys() {
+        p2sh_multisig(1, 16);
+    }
+
+    fn p2wsh_output() -> TxOut {
+        TxOut {
+            value: Amount::from_sat(50_000),
+            script_pubkey: ScriptBuf::new_p2wsh(&WScriptHash::all_zeros()),
+        }
+    }
+
+    #[test]
+    fn predict_transaction_weight_matches_rust_bitcoin() {
+        let pool = build_pool(&[(10_000, p2wpkh().to_wu()), (20_000, p2wpkh().to_wu())]);
+        let outputs = [p2wsh_output()];
+
+        let expected = predict_weight([InputWeightPrediction::P2WPKH_MAX; 2], [34]);
+        assert_eq!(
+            predict_transaction_weight(&pool, &outputs, false),
+            Some(expected)
+        );
+
+        let expected = predict_weight([InputWeightPrediction::P2WPKH_MAX; 2], [34, 22]);
+        assert_eq!(
+            predict_transaction_weight(&pool, &outputs, true),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn predict_transaction_weight_many_inputs() {
+        // 253 inputs need a three byte input count.
+        let pool = build_pool(&[(10_000, p2tr_key_spend().to_wu()); 253]);
+        let change = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+        };
+
+        let expected = predict_weight(
+            [InputWeightPrediction::P2TR_KEY_DEFAULT_SIGHASH; 253],
+            [34, 22],
+        );
+        let outputs = [p2wsh_output(), change];
+        assert_eq!(
+            predict_transaction_weight(&pool, &outputs, false),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn predict_transaction_weight_overflow() {
+        let pool = build_pool(&[(10_000, u64::MAX)]);
+        assert_eq!(predict_transaction_weight(&pool, &[], false), None);
+    }
+
+    #[test]
+    fn padding() {
+        use crate::coin_grinder::{select_coins_coin_grinder, LimitPolicy};
+        use crate::SelectionError;
+        use bitcoin::FeeRate;
+
+        let pool = build_pool(&[(10_000, 268), (10_000, 268)]);
+        let padded = pad(&pool, Weight::from_wu(4));
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+
+        // 432 wu instead of 428 wu, 10 sats more at 2.5 sat/wu.
+        assert_eq!(padded[0].weight(), Some(Weight::from_wu(432)));
+        assert_eq!(
+            padded[0].effective_value(fee_rate),
+            Some(pool[0].effective_value(fee_rate).unwrap() - SignedAmount::from_sat(10))
+        );
+
+        let select = |max_weight| {
+            select_coins_coin_grinder(
+                Amount::from_sat(15_000),
+                Amount::ZERO,
+                Weight::from_wu(max_weight),
+                usize::MAX,
+                fee_rate,
+                LimitPolicy::ReturnBest,
+                &padded,
+            )
+        };
+        let report = select(864).unwrap();
+        assert_eq!(report.weight, Weight::from_wu(864));
+        assert_eq!(report.weight_margin, Weight::from_wu(8));
+        assert_eq!(report.fee, Amount::from_sat(2 * 1_080));
+        assert_eq!(
+            select(863).map(|r| r.weight),
+            Err(SelectionError::MaxWeightExceeded {
+                max_weight: Weight::from_wu(863)
+            })
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Targets and pools summing to about all the bitcoin there will ever be.
+//!
+//! Aggregated sweeps can select for targets close to `MAX_MONEY` from pools worth as
+//! much, so every selector and report is run at that boundary.
+
+use bitcoin::{Amount, FeeRate, OutPoint, Script, Weight};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_bitcoin_coin_selection::coin_grinder::{select_coins_coin_grinder, LimitPolicy};
+use rust_bitcoin_coin_selection::consolidate::select_coins_consolidate;
+use rust_bitcoin_coin_selection::knapsack::select_coins_knapsack;
+use rust_bitcoin_coin_selection::prefix_sum::select_coins_prefix_sum;
+use rust_bitcoin_coin_selection::srd::select_coins_srd;
+use rust_bitcoin_coin_selection::{
+    defaults, feasibility, objective, pair, preview, repair, waste, CoinSelector, Purpose,
+    SelectionError, SelectionReport, UtxoMetadata, WeightedUtxo,
+};
+
+struct Coin(Amount);
+
+impl UtxoMetadata for Coin {
+    fn outpoint(&self) -> OutPoint {
+        OutPoint::null()
+    }
+
+    fn script_pubkey(&self) -> &Script {
+        Script::new()
+    }
+}
+
+impl WeightedUtxo for Coin {
+    fn satisfaction_weight(&self) -> Weight {
+        Weight::from_wu(272)
+    }
+
+    fn value(&self) -> Amount {
+        self.0
+    }
+}
+
+const FEE_RATE: FeeRate = FeeRate::from_sat_per_kwu(250);
+/// The fee for one input at [`FEE_RATE`].
+const INPUT_FEE: Amount = Amount::from_sat(108);
+
+/// 21 coins of a million bitcoin each.
+fn whole_supply() -> Vec<Coin> {
+    (0..21)
+        .map(|_| Coin(Amount::from_sat(1_000_000 * 100_000_000)))
+        .collect()
+}
+
+/// The most a selection from [`whole_supply`] can cover.
+fn spendable() -> Amount {
+    Amount::MAX_MONEY - INPUT_FEE * 21
+}
+
+fn check<U: WeightedUtxo>(report: Result<SelectionReport<'_, U>, SelectionError>) {
+    let report = report.unwrap();
+    assert_eq!(report.selection.len(), 21);
+    assert_eq!(report.input_value, Amount::MAX_MONEY);
+    assert_eq!(report.effective_value, spendable());
+    assert_eq!(report.fee, INPUT_FEE * 21);
+    assert_eq!(report.excess, Amount::ZERO);
+}
+
+fn insufficient<U>(report: Result<SelectionReport<'_, U>, SelectionError>, required: Amount) {
+    assert_eq!(
+        report.map(|_| ()),
+        Err(SelectionError::InsufficientFunds {
+            available: spendable(),
+            required,
+        })
+    );
+}
+
+#[test]
+fn selectors() {
+    let pool = whole_supply();
+    let target = spendable();
+    let mut rng = StdRng::seed_from_u64(42);
+
+    check(select_coins_prefix_sum(target, FEE_RATE, &pool));
+    check(select_coins_consolidate(target, FEE_RATE, &pool));
+    check(select_coins_knapsack(
+        target,
+        Amount::ZERO,
+        None,
+        FEE_RATE,
+        &pool,
+        &mut rng,
+    ));
+    check(select_coins_coin_grinder(
+        target,
+        Amount::ZERO,
+        defaults::MAX_STANDARD_TX_WEIGHT,
+        usize::MAX,
+        FEE_RATE,
+        LimitPolicy::ReturnBest,
+        &pool,
+    ));
+    check(
+        CoinSelector::new(&pool)
+            .target(target)
+            .fee_rate(FEE_RATE)
+            .min_change(Amount::ZERO)
+            .select_with_rng(&mut rng),
+    );
+    check(
+        CoinSelector::new(&pool)
+            .target(target)
+            .fee_rate(FEE_RATE)
+            .purpose(Purpose::SelfTransfer)
+            .select_with_rng(&mut rng),
+    );
+    check(
+        CoinSelector::new(&pool)
+            .target(target)
+            .fee_rate(FEE_RATE)
+            .preset_inputs(&[0, 20])
+            .min_change(Amount::ZERO)
+            .select_with_rng(&mut rng),
+    );
+}
+
+#[test]
+fn targets_beyond_the_pool() {
+    let pool = whole_supply();
+    let mut rng = StdRng::seed_from_u64(42);
+
+    for target in [
+        spendable() + Amount::ONE_SAT,
+        Amount::MAX_MONEY,
+        Amount::MAX,
+    ] {
+        insufficient(select_coins_prefix_sum(target, FEE_RATE, &pool), target);
+        insufficient(select_coins_consolidate(target, FEE_RATE, &pool), target);
+        insufficient(
+            select_coins_knapsack(target, Amount::ZERO, None, FEE_RATE, &pool, &mut rng),
+            target,
+        );
+        insufficient(
+            select_coins_coin_grinder(
+                target,
+                Amount::ZERO,
+                defaults::MAX_STANDARD_TX_WEIGHT,
+                usize::MAX,
+                FEE_RATE,
+                LimitPolicy::ReturnBest,
+                &pool,
+            ),
+            target,
+        );
+        insufficient(
+            CoinSelector::new(&pool)
+                .target(target)
+                .fee_rate(FEE_RATE)
+                .select_with_rng(&mut rng),
+            target,
+        );
+    }
+
+    // The change SRD leaves counts towards what it needs.
+    let target = spendable() - defaults::CHANGE_LOWER;
+    let srd = |change_fee, rng: &mut StdRng| {
+        select_coins_srd(
+            target,
+            defaults::CHANGE_LOWER + change_fee,
+            defaults::MAX_STANDARD_TX_WEIGHT,
+            usize::MAX,
+            FEE_RATE,
+            &pool,
+            rng,
+        )
+    };
+    let report = srd(Amount::ZERO, &mut rng).unwrap();
+    assert_eq!(report.excess, defaults::CHANGE_LOWER);
+    insufficient(
+        srd(Amount::ONE_SAT, &mut rng),
+        spendable() + Amount::ONE_SAT,
+    );
+}
+
+#[test]
+fn change_beyond_the_pool() {
+    let pool = whole_supply();
+    assert_eq!(
+        select_coins_coin_grinder(
+            Amount::MAX_MONEY,
+            Amount::MAX,
+            defaults::MAX_STANDARD_TX_WEIGHT,
+            usize::MAX,
+            FEE_RATE,
+            LimitPolicy::ReturnBest,
+            &pool,
+        )
+        .map(|_| ()),
+        Err(SelectionError::Overflow)
+    );
+}
+
+#[test]
+fn reports() {
+    let pool = whole_supply();
+    let report = select_coins_prefix_sum(spendable(), FEE_RATE, &pool).unwrap();
+    let long_term_fee_rate = FeeRate::from_sat_per_kwu(0);
+
+    let waste = report.waste(long_term_fee_rate, None).unwrap();
+    assert_eq!(waste, (INPUT_FEE * 21).to_signed().unwrap());
+    assert_eq!(
+        waste::selection_waste(
+            report.selection.iter().map(|c| c.utxo),
+            FEE_RATE,
+            long_term_fee_rate,
+            spendable(),
+            None,
+        ),
+        Some(waste)
+    );
+    assert_eq!(
+        report.waste(long_term_fee_rate, Some(Amount::MAX_MONEY)),
+        (INPUT_FEE * 21 + Amount::MAX_MONEY).to_signed().ok()
+    );
+
+    let objective = objective::Objective::MinimizeValueAtRisk;
+    assert_eq!(objective.cost(&report), (INPUT_FEE * 21).to_signed().ok());
+
+    let indices: Vec<usize> = (0..21).collect();
+    let preview = preview::preview_spend(&pool, &indices, FEE_RATE, long_term_fee_rate).unwrap();
+    assert_eq!(preview.spendable_before, spendable());
+    assert_eq!(preview.spendable_after, Amount::ZERO);
+    assert_eq!(preview.long_term_spendable_before, Amount::MAX_MONEY);
+}
+
+#[test]
+fn helpers() {
+    let pool = whole_supply();
+    let half = Amount::from_sat(10 * 1_000_000 * 100_000_000) - INPUT_FEE * 10;
+
+    let first = pair::PairTarget {
+        target: half,
+        fee_rate: FEE_RATE,
+    };
+    let (first, second) = pair::select_pair(first, first, &pool).unwrap();
+    assert_eq!(first.selection.len() + second.selection.len(), 20);
+
+    assert_eq!(
+        feasibility::max_feasible_fee_rate(spendable(), Weight::MAX, &pool),
+        Some(FEE_RATE)
+    );
+    assert_eq!(
+        repair::repair_selection(&pool, &[0], &[], spendable(), FEE_RATE).map(|s| s.len()),
+        Ok(21)
+    );
+}
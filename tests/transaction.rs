@@ -0,0 +1,216 @@
+//! Builds and signs real transactions from selections, checking that the weights and fees
+//! predicted by this crate match what rust-bitcoin produces.
+
+use bitcoin::absolute::LockTime;
+use bitcoin::hashes::Hash;
+use bitcoin::key::{Keypair, TapTweak};
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::transaction::Version;
+use bitcoin::{
+    ecdsa, taproot, Amount, CompressedPublicKey, FeeRate, OutPoint, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, Txid, Weight, Witness,
+};
+use rust_bitcoin_coin_selection::prefix_sum::select_coins_prefix_sum;
+use rust_bitcoin_coin_selection::{predict_transaction_weight, weight, WeightedUtxo};
+
+#[derive(Clone, Copy)]
+enum Kind {
+    P2wpkh,
+    P2tr,
+}
+
+struct Coin {
+    outpoint: OutPoint,
+    txout: TxOut,
+    secret_key: SecretKey,
+    kind: Kind,
+}
+
+impl WeightedUtxo for Coin {
+    fn satisfaction_weight(&self) -> Weight {
+        match self.kind {
+            Kind::P2wpkh => weight::p2wpkh(),
+            Kind::P2tr => weight::p2tr_key_spend(),
+        }
+    }
+
+    fn value(&self) -> Amount {
+        self.txout.value
+    }
+}
+
+fn pool(coins: &[(u64, Kind)]) -> Vec<Coin> {
+    let secp = Secp256k1::new();
+    coins
+        .iter()
+        .enumerate()
+        .map(|(i, &(value, kind))| {
+            let secret_key = SecretKey::from_slice(&[i as u8 + 1; 32]).unwrap();
+            let script_pubkey = match kind {
+                Kind::P2wpkh => {
+                    let public_key = CompressedPublicKey(secret_key.public_key(&secp));
+                    ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash())
+                }
+                Kind::P2tr => {
+                    let keypair = Keypair::from_secret_key(&secp, &secret_key);
+                    ScriptBuf::new_p2tr(&secp, keypair.x_only_public_key().0, None)
+                }
+            };
+            Coin {
+                outpoint: OutPoint {
+                    txid: Txid::from_byte_array([i as u8; 32]),
+                    vout: 0,
+                },
+                txout: TxOut {
+                    value: Amount::from_sat(value),
+                    script_pubkey,
+                },
+                secret_key,
+                kind,
+            }
+        })
+        .collect()
+}
+
+fn p2wpkh_output(value: Amount) -> TxOut {
+    let secp = Secp256k1::new();
+    let secret_key = SecretKey::from_slice(&[0xcc; 32]).unwrap();
+    let public_key = CompressedPublicKey(secret_key.public_key(&secp));
+    TxOut {
+        value,
+        script_pubkey: ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash()),
+    }
+}
+
+/// Signs a transaction spending `inputs` to `outputs`.
+fn sign(inputs: &[&Coin], outputs: Vec<TxOut>) -> Transaction {
+    let secp = Secp256k1::new();
+    let mut tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: inputs
+            .iter()
+            .map(|coin| TxIn {
+                previous_output: coin.outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: outputs,
+    };
+
+    let prevouts: Vec<TxOut> = inputs.iter().map(|coin| coin.txout.clone()).collect();
+    let mut witnesses = Vec::new();
+    let mut cache = SighashCache::new(&tx);
+    for (index, coin) in inputs.iter().enumerate() {
+        let witness = match coin.kind {
+            Kind::P2wpkh => {
+                let sighash = cache
+                    .p2wpkh_signature_hash(
+                        index,
+                        &coin.txout.script_pubkey,
+                        coin.txout.value,
+                        EcdsaSighashType::All,
+                    )
+                    .unwrap();
+                let message = Message::from_digest(sighash.to_byte_array());
+                let signature = ecdsa::Signature {
+                    signature: secp.sign_ecdsa(&message, &coin.secret_key),
+                    sighash_type: EcdsaSighashType::All,
+                };
+                Witness::p2wpkh(&signature, &coin.secret_key.public_key(&secp))
+            }
+            Kind::P2tr => {
+                let sighash = cache
+                    .taproot_key_spend_signature_hash(
+                        index,
+                        &Prevouts::All(&prevouts),
+                        TapSighashType::Default,
+                    )
+                    .unwrap();
+                let message = Message::from_digest(sighash.to_byte_array());
+                let keypair = Keypair::from_secret_key(&secp, &coin.secret_key)
+                    .tap_tweak(&secp, None)
+                    .to_keypair();
+                let signature = taproot::Signature {
+                    signature: secp.sign_schnorr_no_aux_rand(&message, &keypair),
+                    sighash_type: TapSighashType::Default,
+                };
+                Witness::p2tr_key_spend(&signature)
+            }
+        };
+        witnesses.push(witness);
+    }
+    for (input, witness) in tx.input.iter_mut().zip(witnesses) {
+        input.witness = witness;
+    }
+    tx
+}
+
+/// Selects from `pool`, builds the transaction and checks the predictions against it.
+fn check(pool: &[Coin], payment: Amount, fee_rate: FeeRate) {
+    let recipient = p2wpkh_output(payment);
+    let change_placeholder = p2wpkh_output(Amount::ZERO);
+
+    // The target covers the payment plus the fee for everything but the inputs, which
+    // is what the effective values of the inputs pay for.
+    let no_inputs: [&Coin; 0] = [];
+    let base_weight =
+        predict_transaction_weight(no_inputs, &[recipient.clone(), change_placeholder], false)
+            .unwrap();
+    let target = payment + fee_rate.fee_wu(base_weight).unwrap();
+
+    let report = select_coins_prefix_sum(target, fee_rate, pool).unwrap();
+    let inputs: Vec<&Coin> = report.selection.iter().map(|c| c.utxo).collect();
+
+    let change = p2wpkh_output(report.excess);
+    let outputs = vec![recipient, change];
+    let predicted = predict_transaction_weight(inputs.iter().copied(), &outputs, false).unwrap();
+    let tx = sign(&inputs, outputs);
+    let realized = tx.weight();
+
+    // Presets assume 72 byte ECDSA signatures; real ones are one byte shorter about half
+    // of the time. Schnorr signatures are always exactly as predicted.
+    assert!(realized <= predicted);
+    assert!(predicted - realized <= Weight::from_wu(inputs.len() as u64));
+
+    let input_value: Amount = inputs.iter().map(|coin| coin.txout.value).sum();
+    let output_value: Amount = tx.output.iter().map(|txout| txout.value).sum();
+    let fee = input_value - output_value;
+    assert_eq!(input_value, report.input_value);
+    assert!(fee >= fee_rate.fee_wu(realized).unwrap());
+    // Fees are rounded up for each input and for the rest of the transaction.
+    let rounding = Amount::from_sat(inputs.len() as u64 + 1);
+    assert!(fee <= fee_rate.fee_wu(predicted).unwrap() + rounding);
+}
+
+#[test]
+fn p2wpkh_selection() {
+    let pool = pool(&[
+        (120_000, Kind::P2wpkh),
+        (80_000, Kind::P2wpkh),
+        (45_000, Kind::P2wpkh),
+    ]);
+    check(
+        &pool,
+        Amount::from_sat(150_000),
+        FeeRate::from_sat_per_vb_u32(12),
+    );
+}
+
+#[test]
+fn mixed_selection() {
+    let pool = pool(&[
+        (60_000, Kind::P2tr),
+        (55_000, Kind::P2wpkh),
+        (50_000, Kind::P2tr),
+        (20_000, Kind::P2wpkh),
+    ]);
+    check(
+        &pool,
+        Amount::from_sat(150_000),
+        FeeRate::from_sat_per_vb_u32(3),
+    );
+}